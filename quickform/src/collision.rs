@@ -0,0 +1,115 @@
+//! Handling of rendered outputs that land on the same path
+//!
+//! Output paths are often computed from data, so two operations (or two
+//! entities in a bundle) can end up writing the same file. A
+//! [`CollisionPolicy`] decides what happens when they do.
+
+use std::collections::HashSet;
+
+use crate::Error;
+
+/// What to do when an output is written to a path already written in the run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// The later output replaces the earlier one
+    #[default]
+    Overwrite,
+    /// The run fails with [`Error::OutputCollision`]
+    Error,
+    /// The later output is written under a numbered path, so `User.ts`
+    /// becomes `User-1.ts`, then `User-2.ts`, and so on
+    Rename,
+}
+
+impl CollisionPolicy {
+    /// Picks the final paths for a batch of outputs and records them as written
+    ///
+    /// Paths are only recorded if every path in the batch can be claimed, so a
+    /// failing batch leaves `written` untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `written` - Paths already written during the run
+    /// * `paths` - The requested output paths, in write order
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<String>, Error>` - The path to write each output to
+    pub(crate) fn claim<'a>(
+        &self,
+        written: &mut HashSet<String>,
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<String>, Error> {
+        let mut claimed: Vec<String> = Vec::new();
+        for path in paths {
+            let taken = |candidate: &str| {
+                written.contains(candidate) || claimed.iter().any(|c| c == candidate)
+            };
+            let resolved = if !taken(path) {
+                path.to_string()
+            } else {
+                match self {
+                    CollisionPolicy::Overwrite => path.to_string(),
+                    CollisionPolicy::Error => {
+                        return Err(Error::OutputCollision {
+                            path: path.to_string(),
+                        })
+                    }
+                    CollisionPolicy::Rename => (1..)
+                        .map(|n| numbered(path, n))
+                        .find(|candidate| !taken(candidate))
+                        .expect("numbered paths are unbounded"),
+                }
+            };
+            claimed.push(resolved);
+        }
+        written.extend(claimed.iter().cloned());
+        Ok(claimed)
+    }
+}
+
+/// Inserts `-n` before the extension of the file name in `path`
+fn numbered(path: &str, n: usize) -> String {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (Some(dir), name),
+        None => (None, path),
+    };
+    let name = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{stem}-{n}.{extension}"),
+        _ => format!("{name}-{n}"),
+    };
+    match dir {
+        Some(dir) => format!("{dir}/{name}"),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim() {
+        let mut written = HashSet::from(["models/User.ts".to_string()]);
+
+        let paths = CollisionPolicy::Overwrite
+            .claim(&mut written, ["models/User.ts"])
+            .unwrap();
+        assert_eq!(paths, vec!["models/User.ts"]);
+
+        assert!(matches!(
+            CollisionPolicy::Error.claim(&mut written, ["models/Order.ts", "models/User.ts"]),
+            Err(Error::OutputCollision { path }) if path == "models/User.ts"
+        ));
+        assert!(!written.contains("models/Order.ts"));
+
+        let paths = CollisionPolicy::Rename
+            .claim(&mut written, ["models/User.ts", "models/User.ts", "README"])
+            .unwrap();
+        assert_eq!(
+            paths,
+            vec!["models/User-1.ts", "models/User-2.ts", "README"]
+        );
+        assert!(written.contains("models/User-2.ts"));
+    }
+}
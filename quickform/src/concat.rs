@@ -0,0 +1,94 @@
+//! Joining several rendered templates into one document
+
+/// How the sections of a concatenated render are joined
+///
+/// A plain string converts into a separator without headers.
+///
+/// # Examples
+///
+/// ```rust
+/// use quickform::Separator;
+///
+/// let separator = Separator::new("\n\n").with_header("-- {template}\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Separator {
+    separator: String,
+    header: Option<String>,
+}
+
+impl Separator {
+    /// Creates a separator placed between consecutive sections
+    ///
+    /// # Arguments
+    ///
+    /// * `separator` - The text inserted between sections
+    pub fn new(separator: impl Into<String>) -> Self {
+        Self {
+            separator: separator.into(),
+            header: None,
+        }
+    }
+
+    /// Adds a header written before every section
+    ///
+    /// Occurrences of `{template}` in the header are replaced with the path
+    /// of the section's template.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The header text
+    ///
+    /// # Returns
+    ///
+    /// The separator with the header set
+    pub fn with_header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    /// Returns the text written before a section
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The position of the section, starting at 0
+    /// * `template_path` - The template the section was rendered from
+    pub(crate) fn prefix(&self, index: usize, template_path: &str) -> String {
+        let mut prefix = String::new();
+        if index > 0 {
+            prefix.push_str(&self.separator);
+        }
+        if let Some(header) = &self.header {
+            prefix.push_str(&header.replace("{template}", template_path));
+        }
+        prefix
+    }
+}
+
+impl From<&str> for Separator {
+    fn from(separator: &str) -> Self {
+        Self::new(separator)
+    }
+}
+
+impl From<String> for Separator {
+    fn from(separator: String) -> Self {
+        Self::new(separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix() {
+        let plain = Separator::from(";\n");
+        assert_eq!(plain.prefix(0, "a.sql"), "");
+        assert_eq!(plain.prefix(1, "b.sql"), ";\n");
+
+        let headed = plain.with_header("-- {template}\n");
+        assert_eq!(headed.prefix(0, "a.sql"), "-- a.sql\n");
+        assert_eq!(headed.prefix(1, "b.sql"), ";\n-- b.sql\n");
+    }
+}
@@ -0,0 +1,146 @@
+//! Reusable template engine configuration
+//!
+//! Servers often create a short-lived app per request, and every one of them
+//! needs the same filters, functions and globals. An [`EngineConfig`] captures
+//! those extensions once and can be applied to any number of apps.
+
+use std::sync::Arc;
+
+use minijinja::functions::Function;
+use minijinja::value::{FunctionArgs, FunctionResult, Value};
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+/// A step applied to the template environment of an app
+type Setup = Arc<dyn Fn(&mut Environment<'static>) + Send + Sync>;
+
+/// Filters, functions, globals and syntax shared by many apps
+///
+/// The config is cheap to clone; registered extensions are shared, not copied.
+///
+/// # Examples
+///
+/// ```rust
+/// use quickform::{App, EngineConfig};
+///
+/// let config = EngineConfig::new()
+///     .with_filter("shout", |value: String| value.to_uppercase())
+///     .with_global("generator", "quickform");
+///
+/// let first = App::default().with_engine_config(&config);
+/// let second = App::default().with_engine_config(&config);
+/// ```
+#[derive(Clone, Default)]
+pub struct EngineConfig {
+    setup: Vec<Setup>,
+}
+
+impl EngineConfig {
+    /// Creates an empty config
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a filter
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name templates use to apply the filter
+    /// * `filter` - The filter function
+    ///
+    /// # Returns
+    ///
+    /// The config with the filter registered
+    pub fn with_filter<N, F, Rv, Args>(self, name: N, filter: F) -> Self
+    where
+        N: Into<String>,
+        F: Function<Rv, Args> + Clone,
+        Rv: FunctionResult,
+        Args: for<'a> FunctionArgs<'a>,
+    {
+        let name = name.into();
+        self.with_setup(move |env| env.add_filter(name.clone(), filter.clone()))
+    }
+
+    /// Registers a function callable from templates
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name templates call the function by
+    /// * `function` - The function
+    ///
+    /// # Returns
+    ///
+    /// The config with the function registered
+    pub fn with_function<N, F, Rv, Args>(self, name: N, function: F) -> Self
+    where
+        N: Into<String>,
+        F: Function<Rv, Args> + Clone,
+        Rv: FunctionResult,
+        Args: for<'a> FunctionArgs<'a>,
+    {
+        let name = name.into();
+        self.with_setup(move |env| env.add_function(name.clone(), function.clone()))
+    }
+
+    /// Registers a global variable visible to every template
+    ///
+    /// The value is serialized once, when it is registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the global
+    /// * `value` - The serializable value of the global
+    ///
+    /// # Returns
+    ///
+    /// The config with the global registered
+    pub fn with_global<N: Into<String>, V: Serialize>(self, name: N, value: V) -> Self {
+        let name = name.into();
+        let value = Value::from_serialize(value);
+        self.with_setup(move |env| env.add_global(name.clone(), value.clone()))
+    }
+
+    /// Sets how templates treat undefined variables
+    ///
+    /// # Arguments
+    ///
+    /// * `behavior` - The undefined behavior, e.g. `UndefinedBehavior::Strict`
+    ///
+    /// # Returns
+    ///
+    /// The config with the behavior set
+    pub fn with_undefined_behavior(self, behavior: UndefinedBehavior) -> Self {
+        self.with_setup(move |env| env.set_undefined_behavior(behavior))
+    }
+
+    /// Sets the delimiters templates are written with
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax` - The syntax config, built with `SyntaxConfig::builder`
+    ///
+    /// # Returns
+    ///
+    /// The config with the syntax set
+    #[cfg(feature = "custom_syntax")]
+    pub fn with_syntax(self, syntax: minijinja::syntax::SyntaxConfig) -> Self {
+        self.with_setup(move |env| env.set_syntax(syntax.clone()))
+    }
+
+    /// Adds a step to run against the environment when the config is applied
+    fn with_setup<F>(mut self, setup: F) -> Self
+    where
+        F: Fn(&mut Environment<'static>) + Send + Sync + 'static,
+    {
+        self.setup.push(Arc::new(setup));
+        self
+    }
+
+    /// Applies every registered extension to an environment, in order
+    pub(crate) fn apply(&self, env: &mut Environment<'static>) {
+        for setup in &self.setup {
+            setup(env);
+        }
+    }
+}
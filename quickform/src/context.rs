@@ -1,6 +1,8 @@
-use minijinja::Value;
+use minijinja::value::{Value, ValueKind};
 use serde::Serialize;
 
+use crate::Error;
+
 /// A trait for converting types into minijinja template values
 ///
 /// This trait provides a uniform way to convert Rust types into values that can be
@@ -9,7 +11,7 @@ use serde::Serialize;
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```rust,ignore
 /// use serde::Serialize;
 ///
 /// #[derive(Serialize)]
@@ -29,6 +31,35 @@ use serde::Serialize;
 pub trait Context {
     /// Copies the implementing type into a minijinja [Value]
     fn to_value(&self) -> Value;
+
+    /// Copies the implementing type into a minijinja [Value], failing if it
+    /// cannot be represented
+    ///
+    /// [`Context::to_value`] never fails: a type whose serialization fails is
+    /// converted into an invalid value, which only errors once a template
+    /// uses it. This reports the failure immediately instead.
+    fn try_to_value(&self) -> Result<Value, Error> {
+        let value = self.to_value();
+        if value.kind() != ValueKind::Invalid {
+            return Ok(value);
+        }
+        // Invalid values only expose the error they carry through formatting
+        let rendered = value.to_string();
+        let reason = rendered
+            .strip_prefix("<invalid value: ")
+            .and_then(|reason| reason.strip_suffix('>'))
+            .unwrap_or(&rendered);
+        Err(Error::InvalidContext(reason.to_string()))
+    }
+
+    /// Serializes the implementing type as pretty-printed JSON
+    ///
+    /// Unlike [`Context::to_value`], struct fields keep their declaration order.
+    fn to_json(&self) -> serde_json::Result<String>;
+
+    /// Serializes the implementing type as YAML
+    #[cfg(feature = "yaml")]
+    fn to_yaml(&self) -> serde_yaml::Result<String>;
 }
 
 /// Blanket implementation for all types that implement [Serialize]
@@ -39,4 +70,82 @@ impl<T: Serialize> Context for T {
     fn to_value(&self) -> Value {
         Value::from_serialize(self)
     }
+
+    fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    #[cfg(feature = "yaml")]
+    fn to_yaml(&self) -> serde_yaml::Result<String> {
+        serde_yaml::to_string(self)
+    }
+}
+
+/// Deep-merges `overlay` on top of `base`
+///
+/// When both values are maps, keys from both sides are kept and values present
+/// in both are merged recursively. In every other case `overlay` replaces
+/// `base` entirely.
+pub(crate) fn merge_values(base: &Value, overlay: &Value) -> Value {
+    if base.kind() != ValueKind::Map || overlay.kind() != ValueKind::Map {
+        return overlay.clone();
+    }
+
+    let keys = |value: &Value| -> Vec<Value> {
+        value
+            .try_iter()
+            .map(|iter| iter.collect())
+            .unwrap_or_default()
+    };
+
+    let mut merged: Vec<(Value, Value)> = Vec::new();
+    for key in keys(base) {
+        let base_item = base.get_item(&key).unwrap_or_default();
+        let item = match overlay.get_item(&key) {
+            Ok(overlay_item) if !overlay_item.is_undefined() => {
+                merge_values(&base_item, &overlay_item)
+            }
+            _ => base_item,
+        };
+        merged.push((key, item));
+    }
+    for key in keys(overlay) {
+        if !merged.iter().any(|(existing, _)| existing == &key) {
+            let item = overlay.get_item(&key).unwrap_or_default();
+            merged.push((key, item));
+        }
+    }
+
+    Value::from_iter(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_values() {
+        let base = json!({
+            "project": { "name": "shop", "version": "1.0" },
+            "author": "Alice",
+        })
+        .to_value();
+        let overlay = json!({
+            "project": { "version": "2.0" },
+            "entity": "User",
+        })
+        .to_value();
+
+        let merged = merge_values(&base, &overlay);
+        let project = merged.get_attr("project").unwrap();
+        assert_eq!(project.get_attr("name").unwrap().as_str(), Some("shop"));
+        assert_eq!(project.get_attr("version").unwrap().as_str(), Some("2.0"));
+        assert_eq!(merged.get_attr("author").unwrap().as_str(), Some("Alice"));
+        assert_eq!(merged.get_attr("entity").unwrap().as_str(), Some("User"));
+
+        // Non-map overlays replace the base entirely
+        let replaced = merge_values(&base, &Value::from("plain"));
+        assert_eq!(replaced.as_str(), Some("plain"));
+    }
 }
@@ -0,0 +1,81 @@
+//! Directives declared by templates in a leading comment
+//!
+//! A template can describe how it should be wired into an app with a comment
+//! at the very start of its source:
+//!
+//! ```jinja
+//! {# quickform: context=entities #}
+//! {% for entity in entities %}...{% endfor %}
+//! ```
+//!
+//! Directives are whitespace-separated `key=value` pairs. Currently supported:
+//!
+//! - `context`: the name of the named operation whose output is the template's
+//!   context
+//!
+//! Unrecognized or malformed directives are logged as warnings and ignored.
+
+/// The directives declared by a single template
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Directives {
+    /// The named operation whose output feeds the template
+    pub(crate) context: Option<String>,
+}
+
+/// Parses the directives declared by a template's leading comment
+///
+/// # Arguments
+///
+/// * `template_path` - The template's path, used in warnings
+/// * `source` - The template source
+pub(crate) fn parse_directives(template_path: &str, source: &str) -> Directives {
+    let mut directives = Directives::default();
+
+    let Some(comment) = source.trim_start().strip_prefix("{#") else {
+        return directives;
+    };
+    let Some((body, _)) = comment.split_once("#}") else {
+        return directives;
+    };
+    let Some(body) = body.trim().strip_prefix("quickform:") else {
+        return directives;
+    };
+
+    for pair in body.split_whitespace() {
+        match pair.split_once('=') {
+            Some(("context", value)) if !value.is_empty() => {
+                directives.context = Some(value.to_string());
+            }
+            _ => log::warn!(
+                "Ignoring unrecognized quickform directive `{}` in {}",
+                pair,
+                template_path
+            ),
+        }
+    }
+    directives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_directives() {
+        let parsed = parse_directives("a.jinja", "{# quickform: context=entities #}\nbody");
+        assert_eq!(parsed.context.as_deref(), Some("entities"));
+
+        let parsed = parse_directives("b.jinja", "  {#quickform: unknown=1 context=users #}");
+        assert_eq!(parsed.context.as_deref(), Some("users"));
+
+        // Only a leading quickform comment counts
+        assert_eq!(
+            parse_directives("c.jinja", "{# just a comment #}"),
+            Directives::default()
+        );
+        assert_eq!(
+            parse_directives("d.jinja", "text {# quickform: context=entities #}"),
+            Directives::default()
+        );
+    }
+}
@@ -19,14 +19,155 @@ use crate::fs::FSError;
 /// }
 /// ```
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     /// An error occurred while processing templates
     #[error("Template engine error")]
     RenderError(#[from] minijinja::Error),
+    /// A template failed to render, e.g. because it does not exist or has a
+    /// syntax error
+    ///
+    /// `operation` is the index of the failing operation in registration order.
+    /// `line` is the line the engine reported the error at, and `snippet` the
+    /// source around it, taken from the template the error occurred in, which
+    /// may be one included by `template`.
+    #[error(
+        "Failed to render template {template} in operation {operation}{}",
+        snippet.as_deref().map(|snippet| format!("\n{snippet}")).unwrap_or_default()
+    )]
+    Render {
+        template: String,
+        operation: usize,
+        line: Option<usize>,
+        snippet: Option<String>,
+        #[source]
+        source: minijinja::Error,
+    },
+    /// An operation's output could not be converted into a template context,
+    /// e.g. because its `Serialize` implementation failed
+    #[error("Operation output cannot be used as a template context: {0}")]
+    InvalidContext(String),
+    /// An operation takes an extractor parameter the app cannot provide to it
+    #[error("{0} is not available to this operation")]
+    ExtractorUnavailable(&'static str),
     /// An error occurred during file system operations
     #[error("In memory filesystem error")]
     FileSystemError(#[from] FSError),
     /// An error occurred during IO operations
     #[error("IO error")]
     IOError(#[from] std::io::Error),
+    /// A template rendered more output than the configured limit allows
+    #[error("Rendered output of {template} exceeds the limit of {limit} bytes")]
+    OutputTooLarge { template: String, limit: usize },
+    /// An operation required exactly one render operation to be registered
+    #[error("Expected exactly one render operation, found {found}")]
+    ExpectedSingleRender { found: usize },
+    /// No named operation has produced an output under the given name
+    #[error("No output is available from a named operation called '{0}'")]
+    MissingNamedOutput(String),
+    /// Two outputs were written to the same path under `CollisionPolicy::Error`
+    #[error("More than one output was written to {path}")]
+    OutputCollision { path: String },
+    /// No render operation is registered for the given template
+    #[error("No render operation is registered for template {0}")]
+    NoRenderOperation(String),
+    /// A per-item render found no collection of its item type
+    #[error("No collection of {0} was added with with_collection_state")]
+    MissingCollection(String),
+    /// None of the candidate templates of an operation exist
+    #[error("None of the candidate templates exist: {}", tried.join(", "))]
+    NoTemplateAvailable { tried: Vec<String> },
+    /// An operation node depends on a name no node was declared with
+    #[error("Operation '{operation}' depends on unknown operation '{dependency}'")]
+    UnknownDependency {
+        operation: String,
+        dependency: String,
+    },
+    /// The dependencies of operation nodes form a cycle
+    ///
+    /// Lists every operation that could not run, which includes operations
+    /// depending on the cycle as well as those forming it.
+    #[error("Operations {} are part of or depend on a dependency cycle", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+    /// The run was cancelled through its `CancellationToken`
+    #[error("The run was cancelled")]
+    Cancelled,
+    /// An operation did not finish within its timeout
+    #[error("Operation '{label}' timed out after {duration:?}")]
+    OperationTimeout {
+        label: String,
+        duration: std::time::Duration,
+    },
+    /// An operation retried under a `RetryPolicy` failed on every attempt
+    ///
+    /// `source` is the error of the last attempt.
+    #[error("Operation failed after {attempts} attempts")]
+    RetriesExhausted {
+        attempts: u32,
+        #[source]
+        source: Box<Error>,
+    },
+    /// A fallible operation returned an error, aborting the run
+    ///
+    /// `operation` is the index of the failing operation in registration order,
+    /// and `label` its human-readable label: the label it was registered
+    /// with, or else e.g. its template path.
+    #[error("Operation '{label}' failed")]
+    OperationFailed {
+        operation: usize,
+        label: String,
+        #[source]
+        source: Box<Error>,
+    },
+    /// An operation failed with its own error
+    ///
+    /// The original error is kept intact: its message is displayed as is,
+    /// and it can be recovered with `downcast_ref`.
+    #[error(transparent)]
+    Operation(#[from] Box<dyn std::error::Error + Send + Sync>),
+    /// Generated output differs from a golden directory
+    ///
+    /// Holds every difference found, sorted by path.
+    #[error("Output differs from the golden directory in {} files", .0.len())]
+    GoldenMismatch(Vec<crate::DiffEntry>),
+}
+
+impl Error {
+    /// Wraps an error returned by an operation
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The operation's error
+    ///
+    /// # Returns
+    ///
+    /// An [`Error::Operation`] carrying `error`
+    pub fn operation<E>(error: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Error::Operation(Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("request timed out")]
+    struct Timeout(#[source] std::io::Error);
+
+    #[test]
+    fn test_operation_error() {
+        let error = Error::operation(Timeout(std::io::Error::other("connection reset")));
+        assert_eq!(error.to_string(), "request timed out");
+        assert_eq!(error.source().unwrap().to_string(), "connection reset");
+
+        let Error::Operation(inner) = error else {
+            panic!("expected an operation error");
+        };
+        assert!(inner.downcast_ref::<Timeout>().is_some());
+    }
 }
@@ -14,7 +14,7 @@
 //!
 //! # Usage
 //!
-//! ```no_run
+//! ```rust
 //! use quickform::fs::MemFS;
 //!
 //! // Create a new filesystem
@@ -27,8 +27,8 @@
 //! // Read file contents
 //! let content = fs.read_file("templates/hello.txt").unwrap();
 //!
-//! // Write entire filesystem to disk
-//! fs.write_to_disk("output/").unwrap();
+//! // List every file
+//! assert_eq!(fs.walk(), vec!["templates/hello.txt"]);
 //! ```
 //!
 //! # Error Handling
@@ -44,14 +44,23 @@
 //!
 //! The filesystem maintains creation and modification timestamps for all nodes,
 //! supports nested directory structures, and handles both binary and text files.
+//! Timestamps come from a [`Clock`], which can be fixed for reproducible output.
 //! All paths use forward slashes (`/`) as separators regardless of the host OS.
+//! Backslashes (`\`) are accepted as separators too and treated the same way.
+//! `.` and `..` components are resolved, and paths that would escape the root
+//! are rejected with [`FSError::InvalidPath`].
 
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+use crate::state::{Extractor, Extractors};
+
 /// Error types specific to filesystem operations
 #[derive(Error, Debug)]
 pub enum FSError {
@@ -63,6 +72,10 @@ pub enum FSError {
     AlreadyExists(String),
     #[error("{0} not found")]
     NotFound(String),
+    #[error("{0} is not empty")]
+    DirectoryNotEmpty(String),
+    #[error("{0} is not valid UTF-8")]
+    InvalidUtf8(String),
     #[error("System time error: {0}")]
     SystemTimeError(#[from] std::time::SystemTimeError),
     #[error("IO error: {0}")]
@@ -82,13 +95,22 @@ pub(crate) struct FileNode {
     /// Raw content of the file
     content: Vec<u8>,
     /// Unix timestamp of when the file was created
-    #[allow(unused)]
     created: u64,
     /// Unix timestamp of when the file was last modified
-    #[allow(unused)]
     modified: u64,
 }
 
+/// Metadata of a file in the in-memory filesystem, returned by [`MemFS::metadata`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Unix timestamp of when the file was first written
+    pub created: u64,
+    /// Unix timestamp of when the file was last written
+    pub modified: u64,
+    /// Size of the file's content in bytes
+    pub size: usize,
+}
+
 /// Represents a directory in the in-memory filesystem
 #[derive(Debug, Clone)]
 struct DirectoryNode {
@@ -99,31 +121,253 @@ struct DirectoryNode {
     created: u64,
 }
 
+impl DirectoryNode {
+    /// Returns the number of files and their combined size in bytes, counting
+    /// every file below this directory
+    fn file_stats(&self) -> (usize, u64) {
+        self.children
+            .values()
+            .map(|child| match child {
+                FSNode::File(file) => (1, file.content.len() as u64),
+                FSNode::Directory(dir) => dir.file_stats(),
+            })
+            .fold((0, 0), |(count, size), (c, s)| (count + c, size + s))
+    }
+}
+
 /// An in-memory filesystem that can be read from and written to disk
 /// 
 /// This struct provides a virtual filesystem that can be used to manage
 /// templates and generated files in memory before writing them to disk.
 #[derive(Debug, Clone)]
-pub(crate) struct MemFS {
+pub struct MemFS {
     root: DirectoryNode,
+    clock: Clock,
+}
+
+/// The contents of a [`MemFS`] at some point in time
+///
+/// Created with [`MemFS::snapshot`] and applied with [`MemFS::restore`].
+#[derive(Debug, Clone)]
+pub struct MemFSSnapshot {
+    root: DirectoryNode,
+}
+
+/// The source of the timestamps stamped on filesystem nodes
+///
+/// Timestamps are Unix timestamps in seconds. The default clock reads the
+/// system time; fixing it makes generated filesystems reproducible.
+#[derive(Clone)]
+pub struct Clock(Arc<dyn Fn() -> u64 + Send + Sync>);
+
+impl Clock {
+    /// A clock reading the current system time
+    pub fn system() -> Self {
+        Self::from_fn(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        })
+    }
+
+    /// A clock that always returns the same timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - The Unix timestamp to return
+    pub fn fixed(timestamp: u64) -> Self {
+        Self::from_fn(move || timestamp)
+    }
+
+    /// A clock fixed to the `SOURCE_DATE_EPOCH` environment variable
+    ///
+    /// Falls back to the system time when the variable is unset or invalid,
+    /// following the reproducible builds convention.
+    pub fn source_date_epoch() -> Self {
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.trim().parse().ok())
+            .map(Self::fixed)
+            .unwrap_or_else(Self::system)
+    }
+
+    /// A clock backed by a custom function
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Returns the current Unix timestamp
+    pub fn from_fn<F: Fn() -> u64 + Send + Sync + 'static>(now: F) -> Self {
+        Self(Arc::new(now))
+    }
+
+    /// Returns the current timestamp
+    pub fn now(&self) -> u64 {
+        (self.0)()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Clock").field(&self.now()).finish()
+    }
+}
+
+/// A [`MemFS`] handle shared between the app and the template loader
+///
+/// Uses a synchronous lock since templates are loaded from within the
+/// (synchronous) template engine.
+pub(crate) type SharedMemFS = Arc<RwLock<MemFS>>;
+
+/// Access to the app's output filesystem, as an operation parameter
+///
+/// An `Fs` parameter after an operation's state parameters gives the
+/// operation access to the files rendered by earlier operations. Render and
+/// state operations alike can take one, so a state operation can summarize
+/// generated files into state for a later render:
+///
+/// ```rust
+/// use quickform::{fs::Fs, state::Data, App};
+///
+/// let app = App::default()
+///     .with_state(String::new())
+///     .state_operation(|summary: Data<String>, fs: Fs| async move {
+///         let files = fs.with(|fs| fs.walk()).join(", ");
+///         summary.update(|summary| *summary = files).await;
+///     })
+///     .render_operation("index.ts", |summary: Data<String>, fs: Fs| async move {
+///         let schema = fs.read_to_string("schema.json").unwrap_or_default();
+///         format!("{} {}", summary.clone_inner().await, schema.len())
+///     });
+/// ```
+///
+/// # Locking
+///
+/// The filesystem is locked only for the duration of each call, and every
+/// method is synchronous, so the lock can never be held across an `.await`.
+/// The app cannot write rendered output while a [`Fs::with`] or
+/// [`Fs::with_mut`] closure runs, so keep closures short and never call back
+/// into the `Fs` from one.
+///
+/// Files written through an `Fs` are written to disk along with rendered
+/// output, but are not subject to the app's collision policy.
+#[derive(Clone)]
+pub struct Fs(SharedMemFS);
+
+impl Fs {
+    /// Runs a closure with shared access to the filesystem
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A closure that receives a reference to the filesystem
+    ///
+    /// # Returns
+    ///
+    /// The value returned by the closure
+    pub fn with<R>(&self, f: impl FnOnce(&MemFS) -> R) -> R {
+        f(&self.0.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Runs a closure with exclusive access to the filesystem
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A closure that receives a mutable reference to the filesystem
+    ///
+    /// # Returns
+    ///
+    /// The value returned by the closure
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut MemFS) -> R) -> R {
+        f(&mut self.0.write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Writes a file, creating parent directories as needed
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path where the file should be written
+    /// * `content` - Content to write to the file
+    pub fn write_file(&self, path: &str, content: impl Into<Vec<u8>>) -> Result<(), FSError> {
+        self.with_mut(|fs| fs.write_file(path, content.into()))
+    }
+
+    /// Returns a copy of the raw contents of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to read
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, FSError> {
+        self.with(|fs| fs.read_file(path).cloned())
+    }
+
+    /// Returns the contents of a file as a string
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to read
+    ///
+    /// # Returns
+    ///
+    /// The file's contents, or an error if the file does not exist or is not
+    /// valid UTF-8
+    pub fn read_to_string(&self, path: &str) -> Result<String, FSError> {
+        self.with(|fs| fs.read_file_string(path))
+    }
+
+    /// Returns whether a file or directory exists at the specified path
+    pub fn exists(&self, path: &str) -> bool {
+        self.with(|fs| fs.exists(path))
+    }
+}
+
+impl Extractor for Fs {
+    fn extract(extractors: &Extractors) -> Result<Self, crate::Error> {
+        Ok(Fs(extractors.fs.clone()))
+    }
 }
 
 impl MemFS {
     /// Creates a new empty filesystem
-    pub(crate) fn new() -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-
+    pub fn new() -> Self {
+        let clock = Clock::default();
         Self {
             root: DirectoryNode {
                 children: HashMap::new(),
-                created: timestamp,
+                created: clock.now(),
             },
+            clock,
         }
     }
 
+    /// Stamps nodes with timestamps from `clock` from now on
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The clock to read timestamps from
+    ///
+    /// # Returns
+    ///
+    /// The filesystem using the new clock
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Replaces the clock used to stamp nodes
+    ///
+    /// The root directory is restamped so an empty filesystem is fully
+    /// determined by its clock.
+    pub(crate) fn set_clock(&mut self, clock: Clock) {
+        self.root.created = clock.now();
+        self.clock = clock;
+    }
+
     /// Reads an entire directory structure from disk into memory
     ///
     /// # Arguments
@@ -135,7 +379,118 @@ impl MemFS {
     /// A new MemFS instance containing the directory structure
     pub(crate) fn read_from_disk<P: AsRef<Path>>(path: P) -> Result<Self, FSError> {
         let mut fs = MemFS::new();
-        fs.read_directory_recursive("", path)?;
+        fs.read_directory_recursive("", path, None)?;
+        Ok(fs)
+    }
+
+    /// Reads a directory embedded in the binary with `include_dir!` into
+    /// memory
+    ///
+    /// Entries are stored under their path relative to the embedded
+    /// directory, exactly as files read by [`MemFS::read_from_disk`] are
+    /// stored under their path relative to the directory read.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The embedded directory to read
+    ///
+    /// # Returns
+    ///
+    /// A new MemFS instance containing the embedded files and directories
+    #[cfg(feature = "include_dir")]
+    pub(crate) fn read_from_include_dir(dir: &include_dir::Dir<'_>) -> Result<Self, FSError> {
+        fn read_entries(fs: &mut MemFS, dir: &include_dir::Dir<'_>) -> Result<(), FSError> {
+            for entry in dir.entries() {
+                let virtual_path = entry
+                    .path()
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                match entry {
+                    include_dir::DirEntry::Dir(subdir) => {
+                        fs.create_dir(&virtual_path)?;
+                        read_entries(fs, subdir)?;
+                    }
+                    include_dir::DirEntry::File(file) => {
+                        fs.write_file(&virtual_path, file.contents().to_vec())?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        let mut fs = MemFS::new();
+        read_entries(&mut fs, dir)?;
+        Ok(fs)
+    }
+
+    /// Reads the files of a directory structure from disk that `include`
+    /// accepts
+    ///
+    /// `include` is called with the name of every file. Directories are only
+    /// created for the files read, so directories without any accepted file
+    /// are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the directory to read
+    /// * `include` - Whether to read a file, given its name
+    ///
+    /// # Returns
+    ///
+    /// A new MemFS instance containing the accepted files
+    pub(crate) fn read_from_disk_filtered<P: AsRef<Path>>(
+        path: P,
+        include: &dyn Fn(&str) -> bool,
+    ) -> Result<Self, FSError> {
+        let mut fs = MemFS::new();
+        fs.read_directory_recursive("", path, Some(include))?;
+        Ok(fs)
+    }
+
+    /// Reads the entries of a ZIP archive into memory
+    ///
+    /// Entries are stored under their path inside the archive, exactly as
+    /// files read by [`MemFS::read_from_disk`] are stored under their path
+    /// relative to the directory read. File contents are loaded as raw bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the archive to read
+    ///
+    /// # Returns
+    ///
+    /// A new MemFS instance containing the archive's files and directories
+    #[cfg(feature = "zip")]
+    pub(crate) fn read_from_zip<P: AsRef<Path>>(path: P) -> Result<Self, FSError> {
+        use std::io::Read;
+
+        let file = fs::File::open(path)?;
+        let mut archive = zip::ZipArchive::new(file).map_err(std::io::Error::from)?;
+        let mut fs = MemFS::new();
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(std::io::Error::from)?;
+            // Rejects absolute paths and paths escaping the archive root
+            let Some(entry_path) = entry.enclosed_name() else {
+                return Err(FSError::InvalidPath);
+            };
+            let virtual_path = entry_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            if entry.is_dir() {
+                if !fs.is_dir(&virtual_path) {
+                    fs.create_dir(&virtual_path)?;
+                }
+            } else {
+                let mut content = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut content)?;
+                fs.write_file(&virtual_path, content)?;
+            }
+        }
         Ok(fs)
     }
 
@@ -148,13 +503,13 @@ impl MemFS {
     ///
     /// * `path` - Path where the file should be written
     /// * `content` - Raw content to write to the file
-    pub(crate) fn write_file(&mut self, path: &str, content: Vec<u8>) -> Result<(), FSError> {
-        let components: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    pub fn write_file(&mut self, path: &str, content: Vec<u8>) -> Result<(), FSError> {
+        let components = normalize(path)?;
         if components.is_empty() {
             return Err(FSError::InvalidPath);
         }
 
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let timestamp = self.clock.now();
         let mut current = &mut self.root;
 
         // Navigate to parent directory
@@ -199,13 +554,13 @@ impl MemFS {
     /// # Arguments
     ///
     /// * `path` - Path where the directory should be created
-    pub(crate) fn create_dir(&mut self, path: &str) -> Result<(), FSError> {
-        let components: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    pub fn create_dir(&mut self, path: &str) -> Result<(), FSError> {
+        let components = normalize(path)?;
         if components.is_empty() {
             return Err(FSError::InvalidPath);
         }
 
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let timestamp = self.clock.now();
 
         self.create_node(
             &components,
@@ -223,13 +578,12 @@ impl MemFS {
     /// * `components` - Path components leading to the node location
     /// * `node` - The node to create
     fn create_node(&mut self, components: &[&str], node: FSNode) -> Result<(), FSError> {
+        let timestamp = self.clock.now();
         let mut current = &mut self.root;
 
         // Navigate to parent directory
         for &component in components.iter().take(components.len() - 1) {
             if !current.children.contains_key(component) {
-                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
                 current.children.insert(
                     component.to_string(),
                     FSNode::Directory(DirectoryNode {
@@ -265,8 +619,91 @@ impl MemFS {
     /// # Returns
     ///
     /// The raw contents of the file
-    pub(crate) fn read_file(&self, path: &str) -> Result<&Vec<u8>, FSError> {
-        let components: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    pub fn read_file(&self, path: &str) -> Result<&Vec<u8>, FSError> {
+        self.file(path).map(|file| &file.content)
+    }
+
+    /// Reads the contents of a file at the specified path as a string
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to read
+    ///
+    /// # Returns
+    ///
+    /// The contents of the file, or [`FSError::InvalidUtf8`] carrying the
+    /// path if they are not valid UTF-8
+    pub fn read_file_string(&self, path: &str) -> Result<String, FSError> {
+        std::str::from_utf8(self.read_file(path)?)
+            .map(str::to_string)
+            .map_err(|_| FSError::InvalidUtf8(path.to_string()))
+    }
+
+    /// Returns when a file was last modified
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// The Unix timestamp of the file's last write, as read from the clock
+    pub fn modified(&self, path: &str) -> Result<u64, FSError> {
+        self.file(path).map(|file| file.modified)
+    }
+
+    /// Returns the metadata of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// The file's timestamps, as read from the clock, and its size
+    pub fn metadata(&self, path: &str) -> Result<FileMetadata, FSError> {
+        self.file(path).map(|file| FileMetadata {
+            created: file.created,
+            modified: file.modified,
+            size: file.content.len(),
+        })
+    }
+
+    /// Returns whether a file or directory exists at the specified path
+    ///
+    /// The root directory (an empty path) always exists.
+    pub fn exists(&self, path: &str) -> bool {
+        is_root(path) || self.node(path).is_some()
+    }
+
+    /// Returns whether a file exists at the specified path
+    pub fn is_file(&self, path: &str) -> bool {
+        matches!(self.node(path), Some(FSNode::File(_)))
+    }
+
+    /// Returns whether a directory exists at the specified path
+    ///
+    /// The root directory (an empty path) is always a directory.
+    pub fn is_dir(&self, path: &str) -> bool {
+        is_root(path) || matches!(self.node(path), Some(FSNode::Directory(_)))
+    }
+
+    /// Looks up the node at the specified path, or `None` for the root
+    fn node(&self, path: &str) -> Option<&FSNode> {
+        let mut components = normalize(path).ok()?.into_iter();
+        let mut node = self.root.children.get(components.next()?)?;
+        for component in components {
+            match node {
+                FSNode::Directory(dir) => node = dir.children.get(component)?,
+                FSNode::File(_) => return None,
+            }
+        }
+        Some(node)
+    }
+
+    /// Looks up the file node at the specified path
+    fn file(&self, path: &str) -> Result<&FileNode, FSError> {
+        let components = normalize(path)?;
         if components.is_empty() {
             return Err(FSError::InvalidPath);
         }
@@ -274,7 +711,7 @@ impl MemFS {
         let mut current = &self.root;
         for (i, &component) in components.iter().enumerate() {
             match current.children.get(component) {
-                Some(FSNode::File(file)) if i == components.len() - 1 => return Ok(&file.content),
+                Some(FSNode::File(file)) if i == components.len() - 1 => return Ok(file),
                 Some(FSNode::Directory(dir)) if i < components.len() - 1 => current = dir,
                 Some(_) => return Err(FSError::NotFound(format!("Invalid path: {}", path))),
                 None => return Err(FSError::NotFound(format!("{} not found", component))),
@@ -283,101 +720,550 @@ impl MemFS {
         Err(FSError::NotFound(format!("Path not found: {}", path)))
     }
 
-    /// Lists the contents of a directory
+    /// Removes a file from the filesystem
     ///
     /// # Arguments
     ///
-    /// * `path` - Path to the directory to list
+    /// * `path` - Path to the file to remove
     ///
     /// # Returns
     ///
-    /// A vector of names of the directory's contents
-    #[allow(unused)]
-    pub(crate) fn list_dir(&self, path: &str) -> Result<Vec<String>, FSError> {
-        let components: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    /// The raw contents of the removed file
+    pub fn remove_file(&mut self, path: &str) -> Result<Vec<u8>, FSError> {
+        let components = normalize(path)?;
+        let Some((name, parents)) = components.split_last() else {
+            return Err(FSError::InvalidPath);
+        };
 
-        let mut current = &self.root;
-        for component in components {
-            match current.children.get(component) {
+        let mut current = &mut self.root;
+        for &component in parents {
+            match current.children.get_mut(component) {
                 Some(FSNode::Directory(dir)) => current = dir,
                 Some(_) => return Err(FSError::NotADirectory(component.to_string())),
-                None => {
-                    return Err(FSError::NotFound(format!(
-                        "Directory {} not found",
-                        component
-                    )))
-                }
+                None => return Err(FSError::NotFound(format!("{} not found", component))),
             }
         }
 
-        Ok(current.children.keys().cloned().collect())
+        match current.children.get(*name) {
+            Some(FSNode::File(_)) => match current.children.remove(*name) {
+                Some(FSNode::File(file)) => Ok(file.content),
+                _ => unreachable!("node was checked to be a file"),
+            },
+            Some(_) => Err(FSError::NotFound(format!("Invalid path: {}", path))),
+            None => Err(FSError::NotFound(format!("{} not found", name))),
+        }
     }
 
-    /// Recursively reads a directory from disk into memory
+    /// Deletes a file from the filesystem
+    ///
+    /// Unlike [`MemFS::remove_file`], the file's contents are discarded.
     ///
     /// # Arguments
     ///
-    /// * `prefix` - Virtual path prefix for the current directory
-    /// * `path` - Physical path to read from
-    fn read_directory_recursive<P: AsRef<Path>>(
-        &mut self,
-        prefix: &str,
-        path: P,
-    ) -> Result<(), FSError> {
-        let path = path.as_ref();
-        for entry in fs::read_dir(path).map_err(|e| FSError::NotFound(e.to_string()))? {
-            let entry = entry.map_err(|e| FSError::NotFound(e.to_string()))?;
-            let file_type = entry
-                .file_type()
-                .map_err(|e| FSError::NotFound(e.to_string()))?;
-            let name = entry.file_name().to_string_lossy().into_owned();
+    /// * `path` - Path to the file to delete
+    pub fn delete_file(&mut self, path: &str) -> Result<(), FSError> {
+        self.remove_file(path).map(|_| ())
+    }
 
-            let virtual_path = if prefix.is_empty() {
-                name.clone()
-            } else {
-                format!("{}/{}", prefix, name)
-            };
+    /// Removes a directory from the filesystem
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the directory to remove
+    /// * `recursive` - Whether to also remove the directory's contents. If
+    ///   `false`, removing a non-empty directory fails
+    pub fn remove_dir(&mut self, path: &str, recursive: bool) -> Result<(), FSError> {
+        let components = normalize(path)?;
+        let Some((name, parents)) = components.split_last() else {
+            return Err(FSError::InvalidPath);
+        };
 
-            if file_type.is_dir() {
-                self.create_dir(&virtual_path)?;
-                self.read_directory_recursive(&virtual_path, entry.path())?;
-            } else if file_type.is_file() {
-                let content =
-                    fs::read(entry.path()).map_err(|e| FSError::NotFound(e.to_string()))?;
-                self.write_file(&virtual_path, content)?;
+        let mut current = &mut self.root;
+        for &component in parents {
+            match current.children.get_mut(component) {
+                Some(FSNode::Directory(dir)) => current = dir,
+                Some(_) => return Err(FSError::NotADirectory(component.to_string())),
+                None => return Err(FSError::NotFound(format!("{} not found", component))),
             }
         }
-        Ok(())
+
+        match current.children.get(*name) {
+            Some(FSNode::Directory(dir)) if !recursive && !dir.children.is_empty() => {
+                Err(FSError::DirectoryNotEmpty(path.to_string()))
+            }
+            Some(FSNode::Directory(_)) => {
+                current.children.remove(*name);
+                Ok(())
+            }
+            Some(_) => Err(FSError::NotADirectory(path.to_string())),
+            None => Err(FSError::NotFound(format!("{} not found", name))),
+        }
     }
 
-    /// Writes the entire filesystem structure to disk
+    /// Copies a file to another path
+    ///
+    /// Creates parent directories of the destination as needed.
     ///
     /// # Arguments
     ///
-    /// * `path` - Base path where the filesystem should be written
-    pub(crate) fn write_to_disk<P: AsRef<Path>>(&self, path: P) -> Result<(), FSError> {
-        let base_path = path.as_ref();
-        
-        // Create the root directory if it doesn't exist
-        if !base_path.exists() {
-            fs::create_dir_all(base_path).map_err(FSError::IOError)?;
+    /// * `from` - Path to the file to copy
+    /// * `to` - Path to copy the file to
+    /// * `overwrite` - Whether to replace an existing file at `to`. A
+    ///   directory at `to` is never replaced
+    pub fn copy_file(&mut self, from: &str, to: &str, overwrite: bool) -> Result<(), FSError> {
+        let content = self.read_file(from)?.clone();
+        if self.exists(to) && (!overwrite || self.is_dir(to)) {
+            return Err(FSError::AlreadyExists(to.to_string()));
         }
-
-        self.write_node_to_disk("", base_path, &self.root)
+        self.write_file(to, content)
     }
 
-    /// Recursively writes a directory node and its contents to disk
+    /// Moves a file to another path
+    ///
+    /// Creates parent directories of the destination as needed.
     ///
     /// # Arguments
     ///
-    /// * `prefix` - Virtual path prefix for the current node
+    /// * `from` - Path to the file to move
+    /// * `to` - Path to move the file to
+    /// * `overwrite` - Whether to replace an existing file at `to`. A
+    ///   directory at `to` is never replaced
+    pub fn move_file(&mut self, from: &str, to: &str, overwrite: bool) -> Result<(), FSError> {
+        if is_same_path(from, to) {
+            return self.file(from).map(|_| ());
+        }
+        self.copy_file(from, to, overwrite)?;
+        self.delete_file(from)
+    }
+
+    /// Merges another filesystem into this one
+    ///
+    /// Directories present in both are merged recursively. Files of `other`
+    /// are added, replacing files at the same path if `overwrite` is `true`.
+    /// A file and a directory at the same path always conflict. Nothing is
+    /// merged if there is any conflict.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The filesystem to merge in, e.g. an overlay of templates
+    /// * `overwrite` - Whether files of `other` replace existing files
+    pub fn merge(&mut self, other: MemFS, overwrite: bool) -> Result<(), FSError> {
+        if let Some(path) = find_conflict("", &self.root, &other.root, overwrite) {
+            return Err(FSError::AlreadyExists(path));
+        }
+        merge_nodes(&mut self.root, other.root);
+        Ok(())
+    }
+
+    /// Captures the current contents of the filesystem
+    ///
+    /// The snapshot is a copy of the whole tree, so later changes to the
+    /// filesystem don't affect it.
+    ///
+    /// # Returns
+    ///
+    /// A snapshot that can be passed to [`MemFS::restore`]
+    pub fn snapshot(&self) -> MemFSSnapshot {
+        MemFSSnapshot {
+            root: self.root.clone(),
+        }
+    }
+
+    /// Replaces the contents of the filesystem with a snapshot
+    ///
+    /// Everything written since the snapshot was taken is discarded. The
+    /// filesystem keeps its clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - A snapshot taken with [`MemFS::snapshot`]
+    pub fn restore(&mut self, snapshot: MemFSSnapshot) {
+        self.root = snapshot.root;
+    }
+
+    /// Lists the contents of a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the directory to list
+    ///
+    /// # Returns
+    ///
+    /// A vector of names of the directory's contents
+    pub fn list_dir(&self, path: &str) -> Result<Vec<String>, FSError> {
+        let components = normalize(path)?;
+
+        let mut current = &self.root;
+        for component in components {
+            match current.children.get(component) {
+                Some(FSNode::Directory(dir)) => current = dir,
+                Some(_) => return Err(FSError::NotADirectory(component.to_string())),
+                None => {
+                    return Err(FSError::NotFound(format!(
+                        "Directory {} not found",
+                        component
+                    )))
+                }
+            }
+        }
+
+        Ok(current.children.keys().cloned().collect())
+    }
+
+    /// Returns the full virtual path of every file, in sorted order
+    ///
+    /// The whole tree is traversed. Directories are not listed themselves, so
+    /// empty directories do not appear.
+    pub fn walk(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        Self::collect_files("", &self.root, &mut paths);
+        paths.sort();
+        paths
+    }
+
+    /// Returns the combined size in bytes of every file's content
+    pub fn total_size(&self) -> u64 {
+        self.root.file_stats().1
+    }
+
+    /// Returns the number of files in the whole tree
+    ///
+    /// Directories are not counted.
+    pub fn file_count(&self) -> usize {
+        self.root.file_stats().0
+    }
+
+    /// Recursively collects the paths of all files below a directory node
+    fn collect_files(prefix: &str, node: &DirectoryNode, paths: &mut Vec<String>) {
+        for (name, child) in &node.children {
+            let child_path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+
+            match child {
+                FSNode::File(_) => paths.push(child_path),
+                FSNode::Directory(dir) => Self::collect_files(&child_path, dir, paths),
+            }
+        }
+    }
+
+    /// Recursively reads a directory from disk into memory
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Virtual path prefix for the current directory
+    /// * `path` - Physical path to read from
+    fn read_directory_recursive<P: AsRef<Path>>(
+        &mut self,
+        prefix: &str,
+        path: P,
+        include: Option<&dyn Fn(&str) -> bool>,
+    ) -> Result<(), FSError> {
+        let path = path.as_ref();
+        for entry in fs::read_dir(path).map_err(|e| FSError::NotFound(e.to_string()))? {
+            let entry = entry.map_err(|e| FSError::NotFound(e.to_string()))?;
+            let file_type = entry
+                .file_type()
+                .map_err(|e| FSError::NotFound(e.to_string()))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            let virtual_path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+
+            if file_type.is_dir() {
+                // Filtered reads create directories along with their files
+                if include.is_none() {
+                    self.create_dir(&virtual_path)?;
+                }
+                self.read_directory_recursive(&virtual_path, entry.path(), include)?;
+            } else if file_type.is_file() && include.is_none_or(|include| include(&name)) {
+                let content =
+                    fs::read(entry.path()).map_err(|e| FSError::NotFound(e.to_string()))?;
+                self.write_file(&virtual_path, content)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the entire filesystem structure to disk
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Base path where the filesystem should be written
+    pub(crate) fn write_to_disk<P: AsRef<Path>>(&self, path: P) -> Result<(), FSError> {
+        self.write_tree_to_disk(path.as_ref(), false).map(|_| ())
+    }
+
+    /// Writes the filesystem to disk, skipping files whose content on disk is
+    /// already identical
+    ///
+    /// Skipped files are left untouched, so their modification times on disk
+    /// don't change.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Base path where the filesystem should be written
+    ///
+    /// # Returns
+    ///
+    /// How many files were written and how many were skipped
+    pub(crate) fn write_changed_to_disk<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<WriteSummary, FSError> {
+        self.write_tree_to_disk(path.as_ref(), true)
+    }
+
+    /// Writes the filesystem to disk, optionally deleting files on disk that
+    /// are not in the filesystem
+    ///
+    /// When `delete_extraneous` is set, every file under `path` without a
+    /// counterpart in the filesystem is deleted before writing, along with
+    /// directories left empty by the deletion. Nothing outside `path` is
+    /// touched, and symbolic links are removed rather than followed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Base path where the filesystem should be written
+    /// * `delete_extraneous` - Whether to delete files not in the filesystem
+    ///
+    /// # Returns
+    ///
+    /// The sorted paths, relative to `path`, of the files that were deleted
+    pub fn write_to_disk_synced<P: AsRef<Path>>(
+        &self,
+        path: P,
+        delete_extraneous: bool,
+    ) -> Result<Vec<String>, FSError> {
+        let base_path = path.as_ref();
+        let mut removed = Vec::new();
+        if delete_extraneous && base_path.is_dir() {
+            Self::remove_extraneous("", base_path, Some(&self.root), &mut removed)?;
+            removed.sort();
+        }
+        self.write_tree_to_disk(base_path, false)?;
+        Ok(removed)
+    }
+
+    /// Recursively deletes the files below `dir_path` that are not in `node`
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Virtual path prefix for the current directory
+    /// * `dir_path` - Physical path of the current directory
+    /// * `node` - The matching directory node, or `None` if there is none
+    /// * `removed` - Virtual paths of the files deleted so far
+    fn remove_extraneous(
+        prefix: &str,
+        dir_path: &Path,
+        node: Option<&DirectoryNode>,
+        removed: &mut Vec<String>,
+    ) -> Result<(), FSError> {
+        for entry in fs::read_dir(dir_path).map_err(FSError::IOError)? {
+            let entry = entry.map_err(FSError::IOError)?;
+            let file_type = entry.file_type().map_err(FSError::IOError)?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let child_path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            let child = node.and_then(|node| node.children.get(&name));
+
+            if file_type.is_dir() {
+                let dir = match child {
+                    Some(FSNode::Directory(dir)) => Some(dir),
+                    _ => None,
+                };
+                Self::remove_extraneous(&child_path, &entry.path(), dir, removed)?;
+                if dir.is_none() {
+                    fs::remove_dir(entry.path()).map_err(FSError::IOError)?;
+                }
+            } else if !matches!(child, Some(FSNode::File(_))) {
+                fs::remove_file(entry.path()).map_err(FSError::IOError)?;
+                removed.push(child_path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the filesystem to disk so that `path` is either fully updated
+    /// or left untouched
+    ///
+    /// The filesystem is written to a sibling staging directory, along with
+    /// the files of `path` it doesn't replace, which then replaces `path`
+    /// with renames. The staging directory is next to `path`, so the renames
+    /// never cross filesystems. If writing fails, the staging directory is
+    /// removed and `path` is unchanged. Files in `path` that are not in the
+    /// filesystem are kept, as with a regular write: they are hard linked
+    /// into the staging directory where possible and copied otherwise,
+    /// symbolic links are kept as links, and permissions are preserved.
+    ///
+    /// If `path` can't be renamed, e.g. because it is a mount point, the
+    /// write fails with the rename error and `path` is left untouched. If
+    /// the staging directory can't take its place and the previous contents
+    /// can't be moved back either, the error names the hidden sibling they
+    /// were left in. Failing to remove the previous contents of `path` once
+    /// it has been replaced is only logged, as the output is complete by
+    /// then.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Base path where the filesystem should be written
+    pub(crate) fn write_to_disk_atomic<P: AsRef<Path>>(&self, path: P) -> Result<(), FSError> {
+        // Paths such as `.` have no name to derive the sibling paths from
+        let path = match fs::canonicalize(path.as_ref()) {
+            Ok(canonical) => canonical,
+            Err(_) => path.as_ref().to_path_buf(),
+        };
+        let path = path.as_path();
+        let staging = sibling_path(path, "staging")?;
+        let backup = sibling_path(path, "backup")?;
+
+        let mut permissions = Vec::new();
+        let staged = if path.is_dir() {
+            self.stage_existing("", path, &staging, &mut permissions)
+        } else {
+            fs::create_dir_all(&staging).map_err(FSError::IOError)
+        }
+        .and_then(|()| self.write_tree_to_disk(&staging, false))
+        .and_then(|_| {
+            permissions
+                .into_iter()
+                .try_for_each(|(path, permissions)| fs::set_permissions(path, permissions))
+                .map_err(FSError::IOError)
+        });
+        if let Err(err) = staged {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+
+        let existed = path.exists();
+        if existed {
+            if let Err(err) = fs::rename(path, &backup) {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(FSError::IOError(err));
+            }
+        }
+        if let Err(err) = fs::rename(&staging, path) {
+            let _ = fs::remove_dir_all(&staging);
+            if existed {
+                if let Err(restore_err) = fs::rename(&backup, path) {
+                    return Err(FSError::IOError(std::io::Error::new(
+                        restore_err.kind(),
+                        format!(
+                            "failed to replace {}: {err}, and to move its previous contents \
+                             back from {}: {restore_err}",
+                            path.display(),
+                            backup.display()
+                        ),
+                    )));
+                }
+            }
+            return Err(FSError::IOError(err));
+        }
+        if existed {
+            if let Err(err) = fs::remove_dir_all(&backup) {
+                log::warn!(
+                    "failed to remove the previous output at {}: {err}",
+                    backup.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Recreates the directory `from` at `to`, leaving out the files the
+    /// filesystem replaces
+    ///
+    /// Kept files are hard linked, or copied if they can't be, and symbolic
+    /// links are recreated. The permissions of every directory, and of the
+    /// replaced files, are collected into `permissions` so they can be
+    /// applied once the filesystem is written over the staged directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Virtual path of `from` in the filesystem
+    /// * `from` - The directory to recreate
+    /// * `to` - Where to recreate it
+    /// * `permissions` - Permissions to apply after writing
+    fn stage_existing(
+        &self,
+        prefix: &str,
+        from: &Path,
+        to: &Path,
+        permissions: &mut Vec<(PathBuf, fs::Permissions)>,
+    ) -> Result<(), FSError> {
+        fs::create_dir(to).map_err(FSError::IOError)?;
+        let metadata = fs::metadata(from).map_err(FSError::IOError)?;
+        permissions.push((to.to_path_buf(), metadata.permissions()));
+
+        for entry in fs::read_dir(from).map_err(FSError::IOError)? {
+            let entry = entry.map_err(FSError::IOError)?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let child_path = if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}/{name}")
+            };
+            let source = entry.path();
+            let target = to.join(entry.file_name());
+            let file_type = entry.file_type().map_err(FSError::IOError)?;
+
+            if file_type.is_dir() {
+                self.stage_existing(&child_path, &source, &target, permissions)?;
+            } else if self.is_file(&child_path) {
+                // Written afterwards, so it must not be linked to the original
+                if file_type.is_file() {
+                    let metadata = entry.metadata().map_err(FSError::IOError)?;
+                    permissions.push((target, metadata.permissions()));
+                }
+            } else if file_type.is_symlink() {
+                copy_symlink(&source, &target)?;
+            } else if fs::hard_link(&source, &target).is_err() {
+                fs::copy(&source, &target).map_err(FSError::IOError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the whole tree below `base_path`, creating it if needed
+    fn write_tree_to_disk(
+        &self,
+        base_path: &Path,
+        skip_unchanged: bool,
+    ) -> Result<WriteSummary, FSError> {
+        // Create the root directory if it doesn't exist
+        if !base_path.exists() {
+            fs::create_dir_all(base_path).map_err(FSError::IOError)?;
+        }
+
+        let mut summary = WriteSummary::default();
+        self.write_node_to_disk("", base_path, &self.root, skip_unchanged, &mut summary)?;
+        Ok(summary)
+    }
+
+    /// Recursively writes a directory node and its contents to disk
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Virtual path prefix for the current node
     /// * `base_path` - Physical base path where contents should be written
     /// * `node` - The directory node to write
+    /// * `skip_unchanged` - Whether to skip files identical to those on disk
+    /// * `summary` - Counts of the files written and skipped so far
     fn write_node_to_disk(
         &self,
         prefix: &str,
         base_path: &Path,
         node: &DirectoryNode,
+        skip_unchanged: bool,
+        summary: &mut WriteSummary,
     ) -> Result<(), FSError> {
         for (name, child) in &node.children {
             let child_path = if prefix.is_empty() {
@@ -390,11 +1276,16 @@ impl MemFS {
 
             match child {
                 FSNode::File(file) => {
+                    if skip_unchanged && fs::read(&full_path).is_ok_and(|c| c == file.content) {
+                        summary.skipped += 1;
+                        continue;
+                    }
                     fs::write(&full_path, &file.content).map_err(FSError::IOError)?;
+                    summary.written += 1;
                 }
                 FSNode::Directory(dir) => {
                     fs::create_dir_all(&full_path).map_err(FSError::IOError)?;
-                    self.write_node_to_disk(&child_path, &full_path, dir)?;
+                    self.write_node_to_disk(&child_path, &full_path, dir, skip_unchanged, summary)?;
                 }
             }
         }
@@ -402,13 +1293,151 @@ impl MemFS {
     }
 }
 
+/// How many files a write to disk changed, returned by `App::run_incremental`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteSummary {
+    /// Files that were new or whose content changed, and were written
+    pub written: usize,
+    /// Files whose content on disk was already identical, and were skipped
+    pub skipped: usize,
+}
+
+impl WriteSummary {
+    /// Returns the total number of files considered
+    pub fn total(&self) -> usize {
+        self.written + self.skipped
+    }
+}
+
+impl fmt::Display for WriteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} files changed", self.written, self.total())
+    }
+}
+
 impl Default for MemFS {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Returns the path of the first node of `overlay` that cannot be merged
+/// into `base`
+fn find_conflict(
+    prefix: &str,
+    base: &DirectoryNode,
+    overlay: &DirectoryNode,
+    overwrite: bool,
+) -> Option<String> {
+    for (name, node) in &overlay.children {
+        let path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+
+        match (base.children.get(name), node) {
+            (None, _) => {}
+            (Some(FSNode::File(_)), FSNode::File(_)) if overwrite => {}
+            (Some(FSNode::Directory(base)), FSNode::Directory(overlay)) => {
+                if let Some(conflict) = find_conflict(&path, base, overlay, overwrite) {
+                    return Some(conflict);
+                }
+            }
+            _ => return Some(path),
+        }
+    }
+    None
+}
+
+/// Recursively moves the nodes of `overlay` into `base`, replacing files
+fn merge_nodes(base: &mut DirectoryNode, overlay: DirectoryNode) {
+    for (name, node) in overlay.children {
+        match (base.children.get_mut(&name), node) {
+            (Some(FSNode::Directory(base)), FSNode::Directory(overlay)) => {
+                merge_nodes(base, overlay)
+            }
+            (_, node) => {
+                base.children.insert(name, node);
+            }
+        }
+    }
+}
+
+/// Splits a path into its non-empty components
+///
+/// Both `/` and `\` are accepted as separators, so paths built on Windows
+/// address the same nodes as their forward-slash equivalents.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\']).filter(|s| !s.is_empty())
+}
+
+/// Splits a path into components, resolving `.` and `..`
+///
+/// A leading separator refers to the root of the filesystem, so absolute
+/// paths are resolved against it. Returns [`FSError::InvalidPath`] if a `..`
+/// would step above the root.
+fn normalize(path: &str) -> Result<Vec<&str>, FSError> {
+    let mut normalized = Vec::new();
+    for component in components(path) {
+        match component {
+            "." => {}
+            ".." => {
+                normalized.pop().ok_or(FSError::InvalidPath)?;
+            }
+            _ => normalized.push(component),
+        }
+    }
+    Ok(normalized)
+}
+
+/// Distinguishes the sibling paths of concurrent atomic writes in a process
+static SIBLING_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns an unused hidden path next to `path` for a temporary copy of it
+fn sibling_path(path: &Path, purpose: &str) -> Result<PathBuf, FSError> {
+    let name = path.file_name().ok_or(FSError::InvalidPath)?;
+    loop {
+        let sibling = format!(
+            ".{}.quickform-{purpose}-{}-{}",
+            name.to_string_lossy(),
+            std::process::id(),
+            SIBLING_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let sibling = path.with_file_name(sibling);
+        if fs::symlink_metadata(&sibling).is_err() {
+            return Ok(sibling);
+        }
+    }
+}
+
+/// Recreates the symbolic link `from` at `to`, pointing to the same target
+fn copy_symlink(from: &Path, to: &Path) -> Result<(), FSError> {
+    let target = fs::read_link(from).map_err(FSError::IOError)?;
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&target, to);
+    #[cfg(windows)]
+    let linked = if fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(&target, to)
+    } else {
+        std::os::windows::fs::symlink_file(&target, to)
+    };
+    linked.map_err(FSError::IOError)
+}
+
+/// Returns whether a path refers to the root directory
+fn is_root(path: &str) -> bool {
+    normalize(path).is_ok_and(|components| components.is_empty())
+}
+
+/// Returns whether two paths refer to the same node
+fn is_same_path(a: &str, b: &str) -> bool {
+    matches!((normalize(a), normalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
 #[cfg(test)]
+// Tests pass `&Path`s where a `Path` would do
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod tests {
     use super::*;
     use std::fs;
@@ -513,4 +1542,392 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_to_disk_atomic() -> Result<(), FSError> {
+        let temp_dir = tempdir::TempDir::new("fs_test").unwrap();
+        let base_path = temp_dir.path().join("output");
+        fs::create_dir_all(&base_path).unwrap();
+        fs::write(base_path.join("keep.txt"), "keep").unwrap();
+        fs::write(base_path.join("main.rs"), "old").unwrap();
+
+        let mut memfs = MemFS::new();
+        memfs.write_file("main.rs", b"new".to_vec())?;
+        memfs.write_file("src/lib.rs", b"lib".to_vec())?;
+        memfs.write_to_disk_atomic(&base_path)?;
+
+        assert_eq!(fs::read(base_path.join("keep.txt")).unwrap(), b"keep");
+        assert_eq!(fs::read(base_path.join("main.rs")).unwrap(), b"new");
+        assert_eq!(fs::read(base_path.join("src/lib.rs")).unwrap(), b"lib");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        // A failed write leaves the output directory untouched
+        let mut memfs = MemFS::new();
+        memfs.write_file("a.txt", b"a".to_vec())?;
+        memfs.write_file("keep.txt/nested.txt", b"nested".to_vec())?;
+        assert!(memfs.write_to_disk_atomic(&base_path).is_err());
+        assert!(!base_path.join("a.txt").exists());
+        assert_eq!(fs::read(base_path.join("keep.txt")).unwrap(), b"keep");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        // Concurrent writes stage into different directories
+        assert_ne!(
+            sibling_path(&base_path, "staging")?,
+            sibling_path(&base_path, "staging")?
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_to_disk_atomic_keeps_links_and_permissions() -> Result<(), FSError> {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let temp_dir = tempdir::TempDir::new("fs_test").unwrap();
+        let shared = temp_dir.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("notes.txt"), "notes").unwrap();
+        let base_path = temp_dir.path().join("output");
+        fs::create_dir_all(&base_path).unwrap();
+        symlink(&shared, base_path.join("docs")).unwrap();
+        symlink(shared.join("notes.txt"), base_path.join("notes.txt")).unwrap();
+        fs::write(base_path.join("build.sh"), "old").unwrap();
+        fs::set_permissions(
+            base_path.join("build.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        let mut memfs = MemFS::new();
+        memfs.write_file("build.sh", b"new".to_vec())?;
+        memfs.write_to_disk_atomic(&base_path)?;
+
+        assert_eq!(fs::read_link(base_path.join("docs")).unwrap(), shared);
+        assert!(fs::symlink_metadata(base_path.join("notes.txt"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let build = fs::metadata(base_path.join("build.sh")).unwrap();
+        assert_eq!(build.permissions().mode() & 0o777, 0o755);
+        assert_eq!(fs::read(base_path.join("build.sh")).unwrap(), b"new");
+        assert_eq!(fs::read(shared.join("notes.txt")).unwrap(), b"notes");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_to_disk_synced() -> Result<(), FSError> {
+        let temp_dir = tempdir::TempDir::new("fs_test").unwrap();
+        let base_path = temp_dir.path();
+        fs::create_dir_all(base_path.join("src/old")).unwrap();
+        fs::write(base_path.join("src/main.rs"), "old").unwrap();
+        fs::write(base_path.join("src/stale.rs"), "stale").unwrap();
+        fs::write(base_path.join("src/old/gone.rs"), "gone").unwrap();
+
+        let mut fs = MemFS::new();
+        fs.write_file("src/main.rs", b"new".to_vec())?;
+
+        assert!(fs.write_to_disk_synced(base_path, false)?.is_empty());
+        assert!(base_path.join("src/stale.rs").is_file());
+
+        let removed = fs.write_to_disk_synced(base_path, true)?;
+        assert_eq!(removed, vec!["src/old/gone.rs", "src/stale.rs"]);
+        assert!(!base_path.join("src/stale.rs").exists());
+        assert!(!base_path.join("src/old").exists());
+        assert_eq!(fs::read(base_path.join("src/main.rs")).unwrap(), b"new");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("src/models/user.rs", Vec::new())?;
+        fs.write_file("src/lib.rs", Vec::new())?;
+        fs.write_file("Cargo.toml", Vec::new())?;
+        fs.write_file("src/models/mod.rs", Vec::new())?;
+        fs.create_dir("src/empty")?;
+
+        assert_eq!(
+            fs.walk(),
+            vec![
+                "Cargo.toml",
+                "src/lib.rs",
+                "src/models/mod.rs",
+                "src/models/user.rs"
+            ]
+        );
+        assert!(MemFS::new().walk().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_size() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("src/models/user.rs", b"struct User;".to_vec())?;
+        fs.write_file("src/lib.rs", b"mod models;".to_vec())?;
+        fs.write_file("README.md", Vec::new())?;
+        fs.create_dir("src/empty")?;
+
+        assert_eq!(fs.file_count(), 3);
+        assert_eq!(fs.total_size(), 23);
+        assert_eq!(MemFS::new().file_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_file() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("dir/a.txt", b"a".to_vec())?;
+        fs.write_file("dir/b.txt", b"b".to_vec())?;
+
+        assert_eq!(fs.remove_file("dir/a.txt")?, b"a".to_vec());
+        assert_eq!(fs.walk(), vec!["dir/b.txt"]);
+        assert!(fs.remove_file("dir/a.txt").is_err());
+        assert!(fs.remove_file("dir").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_file() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("src/models/User.ts", b"user".to_vec())?;
+        fs.write_file("src/models/Post.ts", b"post".to_vec())?;
+
+        fs.delete_file("src/models/User.ts")?;
+        assert_eq!(fs.walk(), vec!["src/models/Post.ts"]);
+        assert!(matches!(
+            fs.delete_file("src/models/User.ts"),
+            Err(FSError::NotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_dir() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("src/models/User.ts", b"user".to_vec())?;
+        fs.create_dir("src/empty")?;
+
+        assert!(matches!(
+            fs.remove_dir("src/models", false),
+            Err(FSError::DirectoryNotEmpty(_))
+        ));
+        fs.remove_dir("src/empty", false)?;
+        fs.remove_dir("src/models", true)?;
+        assert!(fs.walk().is_empty());
+        assert!(matches!(
+            fs.remove_dir("src/models", true),
+            Err(FSError::NotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exists() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("src/models/User.ts", b"user".to_vec())?;
+
+        assert!(fs.exists("src/models/User.ts"));
+        assert!(fs.is_file("src/models/User.ts"));
+        assert!(!fs.is_dir("src/models/User.ts"));
+
+        assert!(fs.exists("src/models"));
+        assert!(fs.is_dir("src/models/"));
+        assert!(!fs.is_file("src/models"));
+
+        assert!(fs.is_dir(""));
+        assert!(!fs.exists("src/models/Post.ts"));
+        assert!(!fs.exists("src/models/User.ts/child"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backslash_paths() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.create_dir("templates\\partials")?;
+        fs.write_file("templates\\child\\file.jinja", b"child".to_vec())?;
+
+        assert_eq!(fs.walk(), vec!["templates/child/file.jinja"]);
+        assert_eq!(fs.read_file("templates/child/file.jinja")?, b"child");
+        assert_eq!(fs.read_file("templates\\child\\file.jinja")?, b"child");
+        assert!(fs.is_dir("templates/partials"));
+
+        let mut entries = fs.list_dir("templates\\")?;
+        entries.sort();
+        assert_eq!(entries, vec!["child", "partials"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_traversal() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        assert!(matches!(
+            fs.write_file("../evil.txt", b"evil".to_vec()),
+            Err(FSError::InvalidPath)
+        ));
+        assert!(matches!(
+            fs.create_dir("src/../../evil"),
+            Err(FSError::InvalidPath)
+        ));
+
+        fs.write_file("/src/./models/../main.rs", b"main".to_vec())?;
+        assert_eq!(fs.walk(), vec!["src/main.rs"]);
+        assert_eq!(fs.read_file("src/models/../main.rs")?, b"main");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_file() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("config.toml", b"debug = true".to_vec())?;
+
+        fs.copy_file("config.toml", "services/api/config.toml", false)?;
+        assert_eq!(fs.read_file("services/api/config.toml")?, b"debug = true");
+        assert_eq!(fs.read_file("config.toml")?, b"debug = true");
+
+        fs.write_file("config.toml", b"debug = false".to_vec())?;
+        assert!(matches!(
+            fs.copy_file("config.toml", "services/api/config.toml", false),
+            Err(FSError::AlreadyExists(_))
+        ));
+        fs.copy_file("config.toml", "services/api/config.toml", true)?;
+        assert_eq!(fs.read_file("services/api/config.toml")?, b"debug = false");
+
+        assert!(matches!(
+            fs.copy_file("config.toml", "services", true),
+            Err(FSError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            fs.copy_file("missing.toml", "copy.toml", false),
+            Err(FSError::NotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_file() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("scaffold/config.toml", b"debug = true".to_vec())?;
+
+        fs.move_file("scaffold/config.toml", "services/api/config.toml", false)?;
+        assert_eq!(fs.walk(), vec!["services/api/config.toml"]);
+
+        fs.move_file(
+            "services/api/config.toml",
+            "/services/api/config.toml",
+            false,
+        )?;
+        assert_eq!(fs.walk(), vec!["services/api/config.toml"]);
+        assert!(matches!(
+            fs.move_file("scaffold/config.toml", "config.toml", false),
+            Err(FSError::NotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<(), FSError> {
+        let mut base = MemFS::new();
+        base.write_file("src/models/user.rs", b"base user".to_vec())?;
+        base.write_file("src/lib.rs", b"base lib".to_vec())?;
+
+        let mut overlay = MemFS::new();
+        overlay.write_file("src/models/user.rs", b"overlay user".to_vec())?;
+        overlay.write_file("src/models/post.rs", b"overlay post".to_vec())?;
+        overlay.write_file("README.md", b"overlay readme".to_vec())?;
+
+        let mut merged = base.clone();
+        assert!(matches!(
+            merged.merge(overlay.clone(), false),
+            Err(FSError::AlreadyExists(path)) if path == "src/models/user.rs"
+        ));
+        assert_eq!(merged.walk(), base.walk());
+
+        merged.merge(overlay, true)?;
+        assert_eq!(
+            merged.walk(),
+            vec![
+                "README.md",
+                "src/lib.rs",
+                "src/models/post.rs",
+                "src/models/user.rs"
+            ]
+        );
+        assert_eq!(merged.read_file("src/models/user.rs")?, b"overlay user");
+        assert_eq!(merged.read_file("src/lib.rs")?, b"base lib");
+
+        let mut overlay = MemFS::new();
+        overlay.write_file("src/models", b"not a directory".to_vec())?;
+        assert!(merged.merge(overlay, true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_file_string() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("greet.txt", b"Hello!".to_vec())?;
+        fs.write_file("logo.png", vec![0x89, 0x50, 0xff])?;
+
+        assert_eq!(fs.read_file_string("greet.txt")?, "Hello!");
+        assert!(matches!(
+            fs.read_file_string("logo.png"),
+            Err(FSError::InvalidUtf8(path)) if path == "logo.png"
+        ));
+
+        let loader = crate::loader::memfs_loader(Arc::new(RwLock::new(fs)));
+        let err = loader("logo.png").unwrap_err();
+        assert!(err.to_string().contains("logo.png"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<(), FSError> {
+        let mut fs = MemFS::new();
+        fs.write_file("src/lib.rs", b"lib".to_vec())?;
+        let snapshot = fs.snapshot();
+
+        fs.write_file("src/lib.rs", b"changed".to_vec())?;
+        fs.write_file("src/main.rs", b"main".to_vec())?;
+        fs.restore(snapshot);
+
+        assert_eq!(fs.walk(), vec!["src/lib.rs"]);
+        assert_eq!(fs.read_file("src/lib.rs")?, b"lib");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clock() -> Result<(), FSError> {
+        let mut fs = MemFS::new().with_clock(Clock::fixed(1_700_000_000));
+        fs.write_file("dir/a.txt", b"a".to_vec())?;
+        assert_eq!(fs.modified("dir/a.txt")?, 1_700_000_000);
+
+        let mut fs = fs.with_clock(Clock::fixed(1_800_000_000));
+        fs.write_file("dir/a.txt", b"b".to_vec())?;
+        assert_eq!(fs.modified("dir/a.txt")?, 1_800_000_000);
+        assert_eq!(
+            fs.metadata("dir/a.txt")?,
+            FileMetadata {
+                created: 1_700_000_000,
+                modified: 1_800_000_000,
+                size: 1,
+            }
+        );
+        assert!(fs.metadata("dir").is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,99 @@
+//! Golden-file comparison of generated output
+//!
+//! Code generators are usually tested by comparing their output against a
+//! checked-in "golden" directory. This module diffs a generated MemFS against
+//! such a directory.
+
+use std::collections::BTreeSet;
+
+use crate::fs::MemFS;
+
+/// Environment variable that makes golden assertions rewrite the golden files
+pub const UPDATE_GOLDENS_ENV: &str = "QUICKFORM_UPDATE_GOLDENS";
+
+/// A difference between generated output and a golden directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    /// A golden file that was not generated
+    Missing { path: String },
+    /// A generated file that has no golden counterpart
+    Unexpected { path: String },
+    /// A file whose generated content differs from the golden one
+    Changed {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Compares every file of two filesystems
+///
+/// # Arguments
+///
+/// * `expected` - The golden files
+/// * `actual` - The generated files
+///
+/// # Returns
+///
+/// * `Vec<DiffEntry>` - One entry per differing path, sorted by path
+pub(crate) fn diff(expected: &MemFS, actual: &MemFS) -> Vec<DiffEntry> {
+    let paths: BTreeSet<String> = expected.walk().into_iter().chain(actual.walk()).collect();
+
+    paths
+        .into_iter()
+        .filter_map(
+            |path| match (expected.read_file(&path), actual.read_file(&path)) {
+                (Ok(golden), Ok(generated)) if golden == generated => None,
+                (Ok(golden), Ok(generated)) => Some(DiffEntry::Changed {
+                    expected: String::from_utf8_lossy(golden).into_owned(),
+                    actual: String::from_utf8_lossy(generated).into_owned(),
+                    path,
+                }),
+                (Ok(_), Err(_)) => Some(DiffEntry::Missing { path }),
+                (Err(_), _) => Some(DiffEntry::Unexpected { path }),
+            },
+        )
+        .collect()
+}
+
+/// Whether golden assertions should rewrite the golden files
+pub(crate) fn update_requested() -> bool {
+    std::env::var_os(UPDATE_GOLDENS_ENV).is_some_and(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let mut expected = MemFS::new();
+        expected.write_file("same.txt", b"same".to_vec()).unwrap();
+        expected.write_file("changed.txt", b"old".to_vec()).unwrap();
+        expected
+            .write_file("missing.txt", b"gone".to_vec())
+            .unwrap();
+
+        let mut actual = MemFS::new();
+        actual.write_file("same.txt", b"same".to_vec()).unwrap();
+        actual.write_file("changed.txt", b"new".to_vec()).unwrap();
+        actual.write_file("extra/new.txt", b"new".to_vec()).unwrap();
+
+        assert_eq!(
+            diff(&expected, &actual),
+            vec![
+                DiffEntry::Changed {
+                    path: "changed.txt".to_string(),
+                    expected: "old".to_string(),
+                    actual: "new".to_string(),
+                },
+                DiffEntry::Unexpected {
+                    path: "extra/new.txt".to_string(),
+                },
+                DiffEntry::Missing {
+                    path: "missing.txt".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Dependency ordering of operations
+//!
+//! Operations normally run in registration order. Operations declared as
+//! nodes with [`App::node`](crate::App::node) can instead name the operations
+//! they depend on, and [`App::run_graph`](crate::App::run_graph) runs every
+//! operation as soon as its dependencies have finished.
+
+use std::collections::HashMap;
+
+use crate::Error;
+
+/// The name of an operation and the names of the operations it depends on
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub(crate) name: String,
+    pub(crate) dependencies: Vec<String>,
+}
+
+/// Groups operations into waves that can each run concurrently
+///
+/// Every operation runs in the first wave after all of its dependencies, so
+/// operations without dependencies, including those not declared as nodes,
+/// run in the first wave. Waves list operations in registration order.
+///
+/// # Arguments
+///
+/// * `count` - The number of registered operations
+/// * `nodes` - The node of each operation declared as one, by index
+/// * `label` - Names an operation in errors
+///
+/// # Returns
+///
+/// * `Result<Vec<Vec<usize>>, Error>` - The operation indices of each wave,
+///   or an error if a dependency is unknown or the dependencies form a cycle
+pub(crate) fn waves(
+    count: usize,
+    nodes: &HashMap<usize, Node>,
+    label: impl Fn(usize) -> String,
+) -> Result<Vec<Vec<usize>>, Error> {
+    let index_of: HashMap<&str, usize> = nodes
+        .iter()
+        .map(|(&index, node)| (node.name.as_str(), index))
+        .collect();
+    let dependencies = (0..count)
+        .map(|index| {
+            let Some(node) = nodes.get(&index) else {
+                return Ok(Vec::new());
+            };
+            node.dependencies
+                .iter()
+                .map(|dependency| {
+                    index_of.get(dependency.as_str()).copied().ok_or_else(|| {
+                        Error::UnknownDependency {
+                            operation: node.name.clone(),
+                            dependency: dependency.clone(),
+                        }
+                    })
+                })
+                .collect()
+        })
+        .collect::<Result<Vec<Vec<usize>>, Error>>()?;
+
+    let mut done = vec![false; count];
+    let mut waves = Vec::new();
+    while done.contains(&false) {
+        let wave: Vec<usize> = (0..count)
+            .filter(|&index| !done[index] && dependencies[index].iter().all(|&dep| done[dep]))
+            .collect();
+        if wave.is_empty() {
+            let blocked = (0..count)
+                .filter(|&index| !done[index])
+                .map(label)
+                .collect();
+            return Err(Error::DependencyCycle(blocked));
+        }
+        for &index in &wave {
+            done[index] = true;
+        }
+        waves.push(wave);
+    }
+    Ok(waves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, dependencies: &[&str]) -> Node {
+        Node {
+            name: name.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_waves() {
+        let label = |index: usize| index.to_string();
+        let nodes = HashMap::from([
+            (0, node("routes", &["entities", "relationships"])),
+            (1, node("relationships", &["entities"])),
+            (2, node("entities", &[])),
+        ]);
+        assert_eq!(
+            waves(4, &nodes, label).unwrap(),
+            [vec![2, 3], vec![1], vec![0]]
+        );
+
+        let nodes = HashMap::from([(0, node("a", &["b"])), (1, node("b", &["a"]))]);
+        assert!(matches!(
+            waves(3, &nodes, label),
+            Err(Error::DependencyCycle(blocked)) if blocked == ["0", "1"]
+        ));
+
+        let nodes = HashMap::from([(0, node("a", &["missing"]))]);
+        assert!(matches!(
+            waves(1, &nodes, label),
+            Err(Error::UnknownDependency { dependency, .. }) if dependency == "missing"
+        ));
+    }
+}
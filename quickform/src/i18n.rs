@@ -0,0 +1,121 @@
+//! Message catalogs for localized output
+//!
+//! Catalogs are registered per locale and looked up from templates with the
+//! `t` function, e.g. `{{ t("greeting") }}`.
+
+use std::collections::HashMap;
+
+use minijinja::{Error, ErrorKind};
+
+use crate::template::RenderScope;
+
+/// Message catalogs keyed by locale
+///
+/// The locale to render in is chosen per app and passed to each lookup.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Translations {
+    catalogs: HashMap<String, HashMap<String, String>>,
+    default_locale: Option<String>,
+    strict: bool,
+}
+
+impl Translations {
+    /// Whether any catalog has been registered
+    pub(crate) fn is_empty(&self) -> bool {
+        self.catalogs.is_empty()
+    }
+
+    /// Whether a catalog has been registered for a locale
+    pub(crate) fn has_locale(&self, locale: &str) -> bool {
+        self.catalogs.contains_key(locale)
+    }
+
+    /// Adds or replaces the catalog of a locale
+    ///
+    /// The first locale added is used when no locale is selected.
+    pub(crate) fn insert(&mut self, locale: String, catalog: HashMap<String, String>) {
+        self.default_locale.get_or_insert_with(|| locale.clone());
+        self.catalogs.insert(locale, catalog);
+    }
+
+    /// Makes lookups of missing keys fail instead of falling back to the key
+    pub(crate) fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Looks up a key in the catalog of a locale
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - The selected locale, or `None` for the first one added
+    /// * `key` - The message key
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, Error>` - The message, the key itself if it is
+    ///   missing, or an error if the key or the locale's whole catalog is
+    ///   missing in strict mode
+    pub(crate) fn lookup(&self, locale: Option<&str>, key: &str) -> Result<String, Error> {
+        let locale = locale
+            .or(self.default_locale.as_deref())
+            .unwrap_or_default();
+        let Some(catalog) = self.catalogs.get(locale) else {
+            if self.strict {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("no translations are registered for locale '{locale}'"),
+                ));
+            }
+            return Ok(key.to_string());
+        };
+        match catalog.get(key) {
+            Some(message) => Ok(message.clone()),
+            None if self.strict => Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("missing translation for '{key}' in locale '{locale}'"),
+            )),
+            None => Ok(key.to_string()),
+        }
+    }
+}
+
+/// Builds the `t` template function
+///
+/// Messages are looked up in the translations and locale of the app
+/// rendering, which it sets in the render scope.
+pub(crate) fn translate_function(
+) -> impl Fn(&str) -> Result<String, Error> + Clone + Send + Sync + 'static {
+    |key: &str| {
+        RenderScope::with_current(|scope| scope.translations.lookup(scope.locale.as_deref(), key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let mut translations = Translations::default();
+        translations.insert(
+            "en".to_string(),
+            HashMap::from([("greeting".to_string(), "Hello".to_string())]),
+        );
+        translations.insert(
+            "es".to_string(),
+            HashMap::from([("greeting".to_string(), "Hola".to_string())]),
+        );
+
+        assert_eq!(translations.lookup(None, "greeting").unwrap(), "Hello");
+        assert_eq!(translations.lookup(None, "farewell").unwrap(), "farewell");
+        assert_eq!(translations.lookup(Some("es"), "greeting").unwrap(), "Hola");
+        assert_eq!(
+            translations.lookup(Some("fr"), "greeting").unwrap(),
+            "greeting"
+        );
+
+        translations.set_strict(true);
+        assert!(translations.lookup(Some("es"), "farewell").is_err());
+        assert!(translations.lookup(Some("fr"), "greeting").is_err());
+    }
+}
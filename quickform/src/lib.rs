@@ -6,8 +6,8 @@
 //!
 //! # Examples
 //!
-//! ```rust
-//! use quickform::{App};
+//! ```rust,no_run
+//! use quickform::{state::Data, App};
 //!
 //! // Define some state
 //! #[derive(Clone)]
@@ -18,9 +18,10 @@
 //!
 //! // Define an async operation
 //! async fn process_user(user: Data<User>) -> String {
-//!     format!("Hello, {}!", user.name)
+//!     format!("Hello, {}!", user.read().await.name)
 //! }
 //!
+//! # async fn example() -> Result<(), quickform::Error> {
 //! // Create and run the app
 //! let app = App::new()
 //!     .with_templates("templates/")
@@ -28,7 +29,11 @@
 //!         name: "Alice".to_string(),
 //!         age: 30,
 //!     })
-//!     .operation("greet.txt", process_user);
+//!     .render_operation("greet.txt", process_user);
+//!
+//! app.run("output/").await?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! # Features
@@ -43,56 +48,272 @@
 //! - `T`: The type of state stored in the App. Can be:
 //!   - `NoData`: For apps with no state
 //!   - `Data<S>`: For apps with a single state type
-//!   - `(Data<S1>, Data<S2>, ...)`: For apps with multiple state types
+//!   - `(Data<S1>, Data<S2>, ...)`: For apps with multiple state types, up to
+//!     eight. Operations can likewise take at most eight `Data<_>` parameters.
+mod collision;
+mod concat;
+mod config;
 mod context;
+mod directive;
 mod error;
-mod fs;
+pub mod fs;
+mod golden;
+mod graph;
+mod i18n;
 mod loader;
+#[cfg(feature = "markdown")]
+pub mod markdown;
 mod operation;
-mod template;
+mod report;
+mod retry;
+mod run;
+mod runner;
 pub mod state;
+mod template;
+mod transform;
+
+pub use collision::CollisionPolicy;
+pub use concat::Separator;
+pub use config::EngineConfig;
+pub use error::Error;
+pub use golden::{DiffEntry, UPDATE_GOLDENS_ENV};
+pub use report::RunReport;
+pub use retry::RetryPolicy;
+pub use runner::{Runner, StepResult};
+pub use template::Engine;
+pub use tokio_util::sync::CancellationToken;
+pub use transform::Transformer;
 
+use futures::{stream, Stream};
+use minijinja::Value;
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::future::Future;
+use std::io::Write;
 use std::path::Path;
 use std::pin::Pin;
-use serde::Serialize;
-use tokio::sync::RwLock;
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
-use context::Context;
-use error::Error;
-use fs::MemFS;
-use operation::{FunctionSignature, Operation, OperationKind};
-use state::{Data, IntoFunctionParams, NoData};
-use template::TemplateEngine;
+use context::{merge_values, Context};
+use directive::parse_directives;
+use fs::{Clock, MemFS, WriteSummary};
+use i18n::{translate_function, Translations};
+use operation::{
+    EmittedFile, FunctionSignature, Operation, OperationKind, OutputFormat, SharedRenderOperation,
+};
+use run::{Output, OutputSlot, Run};
+use state::{Data, Extractors, IntoFunctionParams, NoData, SharedValues, StateTypes};
+use template::{LimitedWriter, RenderScope, TemplateEngine, TemplateSet};
 
 /// A type alias for Results returned by this library
 type Result<T> = std::result::Result<T, Error>;
 
+/// A template to render against a context, and where the result goes
+struct RenderJob<'a> {
+    template_path: &'a str,
+    output_path: Cow<'a, str>,
+    context: Value,
+}
+
+/// Attributes a template engine error to the template and operation behind it
+///
+/// The source around the line the error was reported at is looked up in
+/// `templates`. Errors in included templates are located in the innermost
+/// template the engine reports.
+fn in_operation(err: Error, templates: &MemFS, template_path: &str, operation: usize) -> Error {
+    match err {
+        Error::RenderError(source) => {
+            let mut located = &source;
+            let mut cause = std::error::Error::source(&source);
+            while let Some(err) = cause {
+                if let Some(inner) = err.downcast_ref::<minijinja::Error>() {
+                    if inner.line().is_some() {
+                        located = inner;
+                    }
+                }
+                cause = err.source();
+            }
+            let line = located.line();
+            let name = located.name().unwrap_or(template_path).to_string();
+            Error::Render {
+                template: template_path.to_string(),
+                operation,
+                line,
+                snippet: line.and_then(|line| source_snippet(templates, &name, line)),
+                source,
+            }
+        }
+        err => err,
+    }
+}
+
+/// Formats the lines around `line` of a template, marking `line` itself
+fn source_snippet(templates: &MemFS, name: &str, line: usize) -> Option<String> {
+    let source = String::from_utf8_lossy(templates.read_file(name).ok()?).into_owned();
+    let lines: Vec<&str> = source.lines().collect();
+    let first = line.saturating_sub(1).max(1);
+    let last = (line + 1).min(lines.len());
+    let width = last.to_string().len();
+    let mut snippet = format!("  --> {name}:{line}");
+    for number in first..=last {
+        let marker = if number == line { ">" } else { " " };
+        let text = lines.get(number - 1)?;
+        snippet.push_str(&format!("\n{marker} {number:>width$} | {text}"));
+    }
+    Some(snippet)
+}
+
+/// Attributes an error returned by an operation's function to the operation
+/// at `index`
+fn operation_failed(err: Error, index: usize, operation: &OperationKind) -> Error {
+    Error::OperationFailed {
+        operation: index,
+        label: operation.label(index),
+        source: Box::new(err),
+    }
+}
+
+/// Strips trailing whitespace from every line and collapses runs of blank
+/// lines into one, normalizing line endings to `\n`
+fn trim_trailing_whitespace(text: &str) -> String {
+    let mut trimmed = String::with_capacity(text.len());
+    let mut previous_blank = false;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() && previous_blank {
+            continue;
+        }
+        previous_blank = line.is_empty();
+        trimmed.push_str(line);
+        trimmed.push('\n');
+    }
+    if !text.ends_with('\n') {
+        trimmed.pop();
+    }
+    trimmed
+}
+
+/// Runs an operation's function and converts its output into a template context
+async fn operation_output(op: &SharedRenderOperation, extractors: &Extractors) -> Result<Value> {
+    op(extractors).await?.try_to_value()
+}
+
+/// Path of the metadata manifest written by `run` when any output has metadata
+pub const META_MANIFEST_PATH: &str = ".quickform-meta.json";
+
+/// Maps a requested template name to the path of a template in the MemFS
+type TemplateResolver = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Called with an operation's label before it runs
+type StartHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Called with an operation's label, duration and outcome after it runs
+type CompleteHook = Arc<dyn Fn(&str, Duration, std::result::Result<(), &Error>) + Send + Sync>;
+
 /// The main application struct that manages state, operations, and template rendering
 ///
+/// Apps are cheap to clone, so one can be stored in shared web-framework state
+/// and run per request. Clones share their state, so state mutations made
+/// through one clone are visible to all others. They also share their
+/// templates and compiled engine until one of them edits its templates or
+/// configures its engine, which first gives it a copy of its own. Everything
+/// else, including operations registered after cloning, belongs to a single
+/// clone.
+///
+/// Each run renders into its own copy of the app's output, which becomes the
+/// output of the app once the run finishes. Concurrent runs, whether of one
+/// app or of its clones, never see each other's files or named outputs.
+///
 /// # Type Parameters
 ///
 /// * `T` - The type of state stored in the App
+#[derive(Clone)]
 pub struct App<T> {
     state: T,
     operations: Vec<OperationKind>,
-    fs: Arc<RwLock<MemFS>>,
-    engine: TemplateEngine<'static>,
+    nodes: HashMap<usize, graph::Node>,
+    templates: Arc<TemplateSet>,
+    output: OutputSlot,
+    shared_context: Option<Value>,
+    output_size_limit: Option<usize>,
+    trim_trailing_whitespace: bool,
+    transformers: Vec<Arc<dyn Transformer>>,
+    template_resolver: Option<TemplateResolver>,
+    start_hooks: Vec<StartHook>,
+    complete_hooks: Vec<CompleteHook>,
+    metadata: BTreeMap<String, serde_json::Value>,
+    collision_policy: CollisionPolicy,
+    translations: Arc<Translations>,
+    locale: Option<String>,
+    collections: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    shared: SharedValues,
+    #[cfg(feature = "markdown")]
+    markdown_transform: Option<markdown::MarkdownTransform>,
 }
 
 impl Default for App<NoData> {
     fn default() -> Self {
-        Self {
-            state: NoData,
-            operations: Vec::new(),
-            fs: Arc::new(RwLock::new(MemFS::new())),
-            engine: TemplateEngine::new(),
-        }
+        Self::with_memfs(MemFS::new())
     }
 }
 
 impl App<NoData> {
+    /// Creates an app without templates or state
+    ///
+    /// Same as [`App::default`]. Templates can be added with
+    /// [`App::with_templates`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an app whose templates and output start out as `fs`
+    fn with_memfs(fs: MemFS) -> Self {
+        let mut app = Self {
+            state: NoData,
+            operations: Vec::new(),
+            nodes: HashMap::new(),
+            templates: Arc::new(TemplateSet::new(fs.clone())),
+            output: OutputSlot::new(fs),
+            shared_context: None,
+            output_size_limit: None,
+            trim_trailing_whitespace: false,
+            transformers: Vec::new(),
+            template_resolver: None,
+            start_hooks: Vec::new(),
+            complete_hooks: Vec::new(),
+            metadata: BTreeMap::new(),
+            collision_policy: CollisionPolicy::default(),
+            translations: Arc::default(),
+            locale: None,
+            collections: HashMap::new(),
+            shared: SharedValues::default(),
+            #[cfg(feature = "markdown")]
+            markdown_transform: None,
+        };
+
+        // `ref("name")` exposes the output of a named operation that already ran
+        let refs = EngineConfig::new().with_function("ref", |name: &str| {
+            RenderScope::with_current(|scope| {
+                scope
+                    .named_outputs
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get(name)
+                    .cloned()
+            })
+            .ok_or_else(|| {
+                minijinja::Error::new(
+                    minijinja::ErrorKind::InvalidOperation,
+                    format!("no named operation called '{name}' has run yet"),
+                )
+            })
+        });
+        app.engine_mut().add_builtins(&refs);
+        app
+    }
+
     /// Configures the app with templates from a directory
     ///
     /// # Arguments
@@ -101,17 +322,116 @@ impl App<NoData> {
     ///
     /// # Returns
     ///
-    /// * `Result<Self>` - The configured App or an error if template loading fails
+    /// The configured App
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be read, for example because it does
+    /// not exist. Use [`App::try_from_dir`] to handle the error instead.
     pub fn from_dir<P: AsRef<Path>>(template_dir: P) -> Self {
-        let fs = MemFS::read_from_disk(template_dir).unwrap_or_default();
-        let engine = TemplateEngine::from_memfs(fs.clone());
-        Self {
-            engine,
-            fs: Arc::new(RwLock::new(fs)),
-            ..Self::default()
+        let template_dir = template_dir.as_ref();
+        Self::try_from_dir(template_dir).unwrap_or_else(|err| {
+            panic!(
+                "failed to load templates from {}: {err}",
+                template_dir.display()
+            )
+        })
+    }
+
+    /// Configures the app with the templates of a directory that have one of
+    /// the given extensions
+    ///
+    /// Other files, such as `.DS_Store` or READMEs, are neither loaded nor
+    /// copied to the output. Extensions are given without the leading dot and
+    /// may have several parts, e.g. `"d.ts"`. Directories without any
+    /// matching file are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_dir` - Path to the directory containing templates
+    /// * `extensions` - The extensions of the files to load
+    ///
+    /// # Returns
+    ///
+    /// The configured App
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be read, for example because it does
+    /// not exist.
+    pub fn from_dir_filtered<P: AsRef<Path>>(template_dir: P, extensions: &[&str]) -> Self {
+        let template_dir = template_dir.as_ref();
+        let include = |name: &str| {
+            extensions.iter().any(|extension| {
+                name.strip_suffix(extension)
+                    .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+            })
+        };
+        match MemFS::read_from_disk_filtered(template_dir, &include) {
+            Ok(fs) => Self::with_memfs(fs),
+            Err(err) => panic!(
+                "failed to load templates from {}: {err}",
+                template_dir.display()
+            ),
         }
     }
 
+    /// Configures the app with templates from a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `template_dir` - Path to the directory containing templates
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self>` - The configured App or an error if the directory
+    ///   cannot be read
+    pub fn try_from_dir<P: AsRef<Path>>(template_dir: P) -> Result<Self> {
+        let fs = MemFS::read_from_disk(template_dir)?;
+        Ok(Self::with_memfs(fs))
+    }
+
+    /// Configures the app with templates from a ZIP archive
+    ///
+    /// Every entry of the archive is loaded under its path inside the
+    /// archive, so a template pack can be used without unpacking it first.
+    /// Binary files are loaded as is and copied through to the output.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - Path to the ZIP archive containing templates
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self>` - The configured App or an error if the archive
+    ///   cannot be read
+    #[cfg(feature = "zip")]
+    pub fn from_zip<P: AsRef<Path>>(archive: P) -> Result<Self> {
+        let fs = MemFS::read_from_zip(archive)?;
+        Ok(Self::with_memfs(fs))
+    }
+
+    /// Configures the app with templates embedded in the binary
+    ///
+    /// The directory is typically embedded with `include_dir!`, so the app
+    /// works without reading templates from disk at runtime. Every file is
+    /// loaded under its path relative to the embedded directory, exactly as
+    /// with [`App::from_dir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The embedded directory containing templates
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self>` - The configured App or an error if the directory
+    ///   contains an invalid path
+    #[cfg(feature = "include_dir")]
+    pub fn from_include_dir(dir: &include_dir::Dir<'_>) -> Result<Self> {
+        let fs = MemFS::read_from_include_dir(dir)?;
+        Ok(Self::with_memfs(fs))
+    }
+
     /// Adds state to the application
     ///
     /// # Type Parameters
@@ -122,23 +442,138 @@ impl App<NoData> {
     ///
     /// * `state` - The state instance to add
     pub fn with_state<S>(self, state: S) -> App<Data<S>> {
-        App {
-            state: Data::new(state),
-            operations: self.operations,
-            fs: self.fs,
-            engine: self.engine,
-        }
+        self.restate(|_| Data::new(state))
+    }
+
+    /// Adds state that is shared with whoever else holds `data`
+    ///
+    /// Unlike [`App::with_state`], the state isn't wrapped in a new `Data`:
+    /// clones of `data`, e.g. the state of another app, see every update made
+    /// by this app's operations, and vice versa.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The state to share
+    pub fn with_shared_state<S>(self, data: Data<S>) -> App<Data<S>> {
+        self.restate(|_| data)
+    }
+
+    /// Registers a render operation that takes no parameters
+    ///
+    /// Equivalent to [`App::render_operation`] for stateless apps, but the
+    /// operation is called directly instead of going through the machinery
+    /// that builds state and extractor parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_static<F, Fut>(mut self, template_path: &str, operation: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: Serialize + 'static,
+    {
+        let op: SharedRenderOperation = Arc::new(move |_: &Extractors| {
+            let fut = operation();
+            Box::pin(async move { Ok(Box::new(fut.await) as Box<dyn Context>) })
+        });
+        self.operations.push(OperationKind::Render {
+            template_path: template_path.to_string(),
+            output_path: template_path.to_string(),
+            op,
+        });
+        self
+    }
+
+    /// Adds a collection to the application
+    ///
+    /// The collection is stored as `Data<Vec<S>>` state, so operations can
+    /// take it like any other state, and can be rendered item by item with
+    /// [`App::render_per_item`].
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items of the collection
+    pub fn with_collection_state<S: Send + Sync + 'static>(
+        mut self,
+        items: Vec<S>,
+    ) -> App<Data<Vec<S>>> {
+        let data = self.register_collection(items);
+        self.restate(|_| data)
     }
 }
 
 impl<S1: Send + Sync + 'static> App<Data<S1>> {
     pub fn with_state<S2>(self, state: S2) -> App<(Data<S1>, Data<S2>)> {
-        App {
-            state: (self.state, Data::new(state)),
-            operations: self.operations,
-            fs: self.fs,
-            engine: self.engine,
-        }
+        self.restate(|prev| (prev, Data::new(state)))
+    }
+
+    pub fn with_shared_state<S2>(self, data: Data<S2>) -> App<(Data<S1>, Data<S2>)> {
+        self.restate(|prev| (prev, data))
+    }
+
+    pub fn with_collection_state<S2: Send + Sync + 'static>(
+        mut self,
+        items: Vec<S2>,
+    ) -> App<(Data<S1>, Data<Vec<S2>>)> {
+        let data = self.register_collection(items);
+        self.restate(|prev| (prev, data))
+    }
+
+    /// Transforms the state into a new state, possibly of another type
+    ///
+    /// Useful between the phases of a multi-phase pipeline. Operations
+    /// registered afterwards receive the new state, while operations
+    /// registered before keep the state they were registered with.
+    ///
+    /// The state is moved into `f` unless it is shared, e.g. with operations
+    /// registered before or through [`App::with_shared_state`], in which case
+    /// `f` receives a copy. To copy shared state without blocking, it must
+    /// not be locked for writing, e.g. through a
+    /// [`DataGuard`](state::DataGuard) held elsewhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A closure building the new state from the current one
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is shared and locked for writing
+    pub fn map_state<S2>(self, f: impl FnOnce(S1) -> S2) -> App<Data<S2>>
+    where
+        S1: Clone,
+    {
+        self.restate(|data| {
+            let state = match Arc::try_unwrap(data.into_rwlock()) {
+                Ok(lock) => lock.into_inner(),
+                Err(shared) => match shared.try_read() {
+                    Ok(state) => state.clone(),
+                    Err(_) => panic!(
+                        "map_state cannot copy the state of type {} while it is locked for \
+                         writing; release the lock before calling it",
+                        std::any::type_name::<S1>()
+                    ),
+                },
+            };
+            Data::new(f(state))
+        })
+    }
+
+    /// Replaces the state with a new state, possibly of another type
+    ///
+    /// Operations registered afterwards receive the new state, while
+    /// operations registered before keep the state they were registered with.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The new state
+    pub fn replace_state<S2>(self, state: S2) -> App<Data<S2>> {
+        self.restate(|_| Data::new(state))
     }
 }
 
@@ -146,12 +581,22 @@ macro_rules! impl_app_with_state {
     (($($idx:tt),*); $($prev:ident),*; $next:ident) => {
         impl<$($prev: Send + Sync + 'static,)*> App<($(Data<$prev>,)*)> {
             pub fn with_state<$next>(self, state: $next) -> App<($(Data<$prev>,)* Data<$next>)> {
-                App {
-                    state: ($(self.state.$idx,)* Data::new(state)),
-                    operations: self.operations,
-                    fs: self.fs,
-                    engine: self.engine,
-                }
+                self.restate(|prev| ($(prev.$idx,)* Data::new(state)))
+            }
+
+            pub fn with_shared_state<$next>(
+                self,
+                data: Data<$next>,
+            ) -> App<($(Data<$prev>,)* Data<$next>)> {
+                self.restate(|prev| ($(prev.$idx,)* data))
+            }
+
+            pub fn with_collection_state<$next: Send + Sync + 'static>(
+                mut self,
+                items: Vec<$next>,
+            ) -> App<($(Data<$prev>,)* Data<Vec<$next>>)> {
+                let data = self.register_collection(items);
+                self.restate(|prev| ($(prev.$idx,)* data))
             }
         }
     };
@@ -160,368 +605,5111 @@ macro_rules! impl_app_with_state {
 impl_app_with_state!((0); S1; S2);
 impl_app_with_state!((0, 1); S1, S2; S3);
 impl_app_with_state!((0, 1, 2); S1, S2, S3; S4);
+impl_app_with_state!((0, 1, 2, 3); S1, S2, S3, S4; S5);
+impl_app_with_state!((0, 1, 2, 3, 4); S1, S2, S3, S4, S5; S6);
+impl_app_with_state!((0, 1, 2, 3, 4, 5); S1, S2, S3, S4, S5, S6; S7);
+impl_app_with_state!((0, 1, 2, 3, 4, 5, 6); S1, S2, S3, S4, S5, S6, S7; S8);
 
-impl<T: Send + Sync + Clone + 'static> App<T> {
-    /// Registers a render operation with the application
-    ///
-    /// # Type Parameters
+impl<T> App<T> {
+    /// Rebuilds the app around a new state value, keeping everything else
+    fn restate<U>(self, f: impl FnOnce(T) -> U) -> App<U> {
+        App {
+            state: f(self.state),
+            operations: self.operations,
+            nodes: self.nodes,
+            templates: self.templates,
+            output: self.output,
+            shared_context: self.shared_context,
+            output_size_limit: self.output_size_limit,
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            transformers: self.transformers,
+            template_resolver: self.template_resolver,
+            start_hooks: self.start_hooks,
+            complete_hooks: self.complete_hooks,
+            metadata: self.metadata,
+            collision_policy: self.collision_policy,
+            translations: self.translations,
+            locale: self.locale,
+            collections: self.collections,
+            shared: self.shared,
+            #[cfg(feature = "markdown")]
+            markdown_transform: self.markdown_transform,
+        }
+    }
+
+    /// Stores a collection so per-item renders of its item type can find it
+    fn register_collection<S: Send + Sync + 'static>(&mut self, items: Vec<S>) -> Data<Vec<S>> {
+        let data = Data::new(items);
+        self.collections
+            .insert(TypeId::of::<S>(), Arc::new(data.clone()));
+        data
+    }
+
+    /// Sets a context shared as the base for all renders
     ///
-    /// * `FSig` - The function signature of the operation
-    /// * `F` - The operation type
+    /// The value is serialized once and deep-merged under the output of every
+    /// render operation, so operations only need to return what is specific to
+    /// them. On key collisions the operation's output wins; nested maps are
+    /// merged recursively.
     ///
     /// # Arguments
     ///
-    /// * `template_path` - The path to the template file
-    /// * `operation` - The operation function to register
+    /// * `value` - The serializable shared context, usually a struct or map
     ///
     /// # Returns
     ///
-    /// The App instance with the new operation registered
-    pub fn render_operation<FSig, F>(mut self, template_path: &str, operation: F) -> Self
-    where
-        FSig: FunctionSignature + 'static,
-        F: Operation<FSig> + Copy + Send + Sync + 'static,
-        F::Future: Send + 'static,
-        FSig::Output: Serialize,
-        T: IntoFunctionParams<FSig>,
-    {
-        let state = self.state.clone();
-        let wrapped_op = move || {
-            let params = state.clone().into_params();
-            let fut = operation.invoke(params);
-            Box::pin(async move {
-                let result = fut.await;
-                Box::new(result) as Box<dyn Context>
-            }) as Pin<Box<dyn Future<Output = _> + Send>>
-        };
+    /// The App instance with the shared context set
+    pub fn with_shared_render_context<C: Serialize>(mut self, value: C) -> Self {
+        self.shared_context = Some(value.to_value());
+        self
+    }
 
-        self.operations.push(OperationKind::Render(
-            template_path.to_string(),
-            Box::new(wrapped_op),
-        ));
+    /// Adds a global variable available to every render
+    ///
+    /// The value is added under `key` to the shared render context, so it is
+    /// merged under the output of every render operation. On key collisions
+    /// the operation's output wins. Calling [`App::with_shared_render_context`]
+    /// afterwards replaces the shared context, globals included.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The variable name templates refer to
+    /// * `value` - The serializable value of the variable
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the global added
+    pub fn with_global<V: Serialize>(mut self, key: &str, value: V) -> Self {
+        let global = Value::from_iter([(key.to_string(), value.to_value())]);
+        self.shared_context = Some(match &self.shared_context {
+            Some(shared) => merge_values(shared, &global),
+            None => global,
+        });
         self
     }
 
-    /// Registers a state operation with the application
+    /// Exposes environment variables to every render as the `env` global
     ///
-    /// # Type Parameters
+    /// Variables are read once, when this is called, and added under their
+    /// full name, so `QUICKFORM_DATABASE_URL` is available to templates as
+    /// `{{ env.QUICKFORM_DATABASE_URL }}`. Variables whose name or value is
+    /// not valid UTF-8 are skipped.
     ///
-    /// * `FSig` - The function signature of the operation
-    /// * `F` - The operation type
+    /// # Security
+    ///
+    /// Anything exposed can end up in rendered files, which are often
+    /// committed or published. Environments commonly hold secrets such as
+    /// API keys and tokens, so pass a prefix reserved for values meant to be
+    /// rendered instead of `None`, which exposes the whole environment.
     ///
     /// # Arguments
     ///
-    /// * `operation` - The operation function to register
+    /// * `prefix` - Only variables whose name starts with it are exposed, or
+    ///   every variable if `None`
     ///
     /// # Returns
     ///
-    /// The App instance with the new operation registered
-    pub fn state_operation<FSig, F>(mut self, operation: F) -> Self
-    where
-        FSig: FunctionSignature + 'static,
-        F: Operation<FSig> + Copy + Send + Sync + 'static,
-        F::Future: Send + 'static,
-        FSig::Output: Send + 'static,
-        T: IntoFunctionParams<FSig>,
-    {
-        let state = self.state.clone();
-        let wrapped_op = move || {
-            let params = state.clone().into_params();
-            let fut = operation.invoke(params);
-            Box::pin(async move {
-                fut.await;
-                ()
-            }) as Pin<Box<dyn Future<Output = ()> + Send>>
-        };
+    /// The App instance with the `env` global added
+    pub fn with_env_context(self, prefix: Option<&str>) -> Self {
+        let env: BTreeMap<String, String> = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .filter(|(name, _)| prefix.is_none_or(|prefix| name.starts_with(prefix)))
+            .collect();
+        self.with_global("env", env)
+    }
 
-        self.operations.push(OperationKind::State(Box::new(wrapped_op)));
+    /// Applies a reusable engine configuration to the app
+    ///
+    /// The filters, functions, globals and syntax captured by `config` are
+    /// registered with the app's template engine. The same config can be
+    /// applied to many apps.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The engine configuration to apply
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the configuration applied
+    pub fn with_engine_config(mut self, config: &EngineConfig) -> Self {
+        self.engine_mut().configure(config);
         self
     }
 
-    /// Executes all registered operations and renders their results
+    /// Selects the template language templates are rendered with
+    ///
+    /// MiniJinja is the default. With the `tera` feature enabled,
+    /// [`Engine::Tera`] renders templates written for Tera instead. Tera
+    /// renders fail if the app was given a MiniJinja only configuration,
+    /// such as with [`App::with_engine_config`] or
+    /// [`App::with_strict_undefined`].
+    ///
+    /// # Arguments
+    ///
+    /// * `engine` - The template engine to render with
     ///
     /// # Returns
     ///
-    /// * `Result<()>` - Success or an error if any operation fails
-    pub async fn run<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
-        for operation in &self.operations {
-            match operation {
-                OperationKind::Render(template_path, op) => {
-                    let context = op().await;
-                    let rendered = self.engine.render(template_path, &context.to_value())?;
-                    self.fs.write().await.write_file(template_path, rendered.as_bytes().to_vec())?;
-                }
-                OperationKind::State(op) => {
-                    op().await;
-                }
-            }
-        }
-        
-        self.fs.write().await.write_to_disk(output_dir.as_ref())?;
-        Ok(())
+    /// The App instance rendering with `engine`
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        let templates = self.template_set_mut();
+        templates.engine.set_engine(engine, templates.fs.clone());
+        self
     }
-}
-
-// Test implementation
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::time::Duration;
-    use std::collections::HashMap;
 
-    #[derive(Clone, serde::Serialize)]
-    struct User {
-        name: String,
-        age: u32,
+    /// Makes referencing an undefined variable a render error
+    ///
+    /// By default undefined variables render as empty, so a typo such as
+    /// `{{ naem }}` silently produces blank output. With strict undefined
+    /// behavior, `run` fails with the template error instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether undefined variables are errors
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the undefined behavior set
+    pub fn with_strict_undefined(self, strict: bool) -> Self {
+        let behavior = if strict {
+            minijinja::UndefinedBehavior::Strict
+        } else {
+            minijinja::UndefinedBehavior::Lenient
+        };
+        self.with_engine_config(&EngineConfig::new().with_undefined_behavior(behavior))
     }
 
-    #[derive(Clone, serde::Serialize)]
-    struct Config {
-        timeout: Duration,
+    /// Sets the delimiters templates are written with
+    ///
+    /// Useful when templates target a language whose syntax collides with
+    /// the default `{{ }}` and `{% %}` delimiters.
+    ///
+    /// # Arguments
+    ///
+    /// * `syntax` - The syntax config, built with `SyntaxConfig::builder`
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the syntax set
+    #[cfg(feature = "custom_syntax")]
+    pub fn with_syntax(self, syntax: minijinja::syntax::SyntaxConfig) -> Self {
+        self.with_engine_config(&EngineConfig::new().with_syntax(syntax))
     }
 
+    /// Registers a message catalog for a locale
+    ///
+    /// Templates look messages up with `{{ t("key") }}`. A key missing from
+    /// the active locale renders as the key itself, unless strict
+    /// translations are enabled. The first locale registered is active until
+    /// another is selected with `with_locale`.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - The locale the catalog belongs to, e.g. `"en"`
+    /// * `catalog` - Messages keyed by message key
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the catalog registered
+    pub fn with_translations(
+        mut self,
+        locale: impl Into<String>,
+        catalog: HashMap<String, String>,
+    ) -> Self {
+        let first = self.translations.is_empty();
+        Arc::make_mut(&mut self.translations).insert(locale.into(), catalog);
+        if first {
+            let config = EngineConfig::new().with_function("t", translate_function());
+            self.engine_mut().add_builtins(&config);
+        }
+        self
+    }
+
+    /// Selects the locale templates are rendered in
+    ///
+    /// Only this app renders in the selected locale: its clones keep theirs,
+    /// so one app can serve several locales by selecting one per clone.
+    ///
+    /// A locale without a catalog renders every key as the key itself, and
+    /// is logged as a warning. With strict translations, rendering `t` in
+    /// such a locale fails instead. Register catalogs before selecting one.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - A locale registered with `with_translations`
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the locale selected
+    pub fn with_locale(mut self, locale: impl Into<String>) -> Self {
+        let locale = locale.into();
+        if !self.translations.has_locale(&locale) {
+            log::warn!("no translations are registered for locale '{locale}'");
+        }
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Makes `t` fail the render when a key is missing from the active locale
+    ///
+    /// # Returns
+    ///
+    /// The App instance with strict translations enabled
+    pub fn with_strict_translations(mut self) -> Self {
+        Arc::make_mut(&mut self.translations).set_strict(true);
+        self
+    }
+
+    /// Limits the size of any single rendered file
+    ///
+    /// Rendering is aborted as soon as a template produces more than `bytes`
+    /// bytes of output, and `run` fails with [`Error::OutputTooLarge`]. This
+    /// guards against runaway loops in templates. By default output size is
+    /// unlimited.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The maximum size of a rendered file in bytes
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the limit set
+    pub fn with_output_size_limit(mut self, bytes: usize) -> Self {
+        self.output_size_limit = Some(bytes);
+        self
+    }
+
+    /// Normalizes whitespace in rendered files before they are written
+    ///
+    /// When enabled, trailing whitespace is stripped from every line of each
+    /// rendered file, runs of blank lines, such as those left by control
+    /// blocks, are collapsed into a single blank line, and line endings are
+    /// normalized to `\n`. Only rendered text is affected: binary and other
+    /// files copied through from the templates are written unchanged.
+    /// Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `trim` - Whether to normalize whitespace
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the setting applied
+    pub fn with_trim_trailing_whitespace(mut self, trim: bool) -> Self {
+        self.trim_trailing_whitespace = trim;
+        self
+    }
+
+    /// Adds a transformer rewriting every rendered file before it is written
+    ///
+    /// Transformers run in registration order after each render, each
+    /// receiving the output of the previous one, and after the built-in
+    /// Markdown transform and whitespace trimming. Files copied through from
+    /// the templates are not transformed. An error returned by a transformer
+    /// aborts the run.
+    ///
+    /// # Arguments
+    ///
+    /// * `transformer` - The transformer to add
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the transformer added
+    pub fn with_transformer<X: Transformer + 'static>(mut self, transformer: X) -> Self {
+        self.transformers.push(Arc::new(transformer));
+        self
+    }
+
+    /// Sets what happens when two outputs are written to the same path
+    ///
+    /// Paths written during a run are tracked, and a later output landing on
+    /// an already written path is handled according to `policy`. By default
+    /// the later output overwrites the earlier one.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - How to handle colliding output paths
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the policy set
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// Sets how requested template names map to templates in the MemFS
+    ///
+    /// Before each render the resolver is called with the template name the
+    /// operation was registered with, and the template at the returned path
+    /// is rendered instead. Returning `None` keeps the requested name. The
+    /// rendered output is still written to the requested name. This enables
+    /// theme overrides and default templates; the default resolver is the
+    /// identity.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - Maps a requested name to an actual template path
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the resolver set
+    pub fn with_template_resolver<R>(mut self, resolver: R) -> Self
+    where
+        R: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.template_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Adds an immutable value operations can take as a [`Shared`](state::Shared) parameter
+    ///
+    /// Unlike state, the value is not part of the app's type and is never
+    /// locked: operations read it through a `Shared<S>` extractor parameter
+    /// following their state parameters. Adding a second value of the same
+    /// type replaces the first.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to share with operations
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the value added
+    pub fn with_shared<S: Send + Sync + 'static>(mut self, value: S) -> Self {
+        Arc::make_mut(&mut self.shared).insert(TypeId::of::<S>(), Arc::new(value));
+        self
+    }
+
+    /// Adds a callback called before each operation runs
+    ///
+    /// The callback receives the operation's label: the label it was
+    /// registered with, or else e.g. its template path. It is called by every
+    /// way of running the app, including [`App::run_parallel`], where
+    /// concurrent renders may report in any order.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - Called with the label of each operation about to run
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the callback added
+    pub fn on_operation_start<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.start_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Adds a callback called after each operation runs
+    ///
+    /// The callback receives the operation's label, how long it took,
+    /// including rendering its output, and whether it succeeded. It is also
+    /// called for the operation that fails a run, before the error is
+    /// returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - Called with the label, duration and outcome of each
+    ///   operation that ran
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the callback added
+    pub fn on_operation_complete<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str, Duration, std::result::Result<(), &Error>) + Send + Sync + 'static,
+    {
+        self.complete_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Resolves a requested template name to the template path to render
+    fn resolve_template(&self, template_path: &str) -> String {
+        self.template_resolver
+            .as_ref()
+            .and_then(|resolve| resolve(template_path))
+            .unwrap_or_else(|| template_path.to_string())
+    }
+
+    /// Registers a transform applied to every rendered Markdown file
+    ///
+    /// After a Markdown file is rendered, its content is parsed into
+    /// [`pulldown_cmark`] events and passed to `transform` together with the
+    /// output path. The returned events are serialized back to Markdown and
+    /// written instead. See the [`markdown`] module for which files count as
+    /// Markdown.
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - Rewrites the events of a rendered file
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the transform registered
+    #[cfg(feature = "markdown")]
+    pub fn with_markdown_transform<F>(mut self, transform: F) -> Self
+    where
+        F: for<'a> Fn(&str, Vec<pulldown_cmark::Event<'a>>) -> Vec<pulldown_cmark::Event<'a>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.markdown_transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Returns the type names of the states held by the app
+    ///
+    /// Names are listed in registration order, which is also the order
+    /// operations must take the states in.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<&'static str>` - One name per state, empty for a stateless app
+    pub fn state_type_names(&self) -> Vec<&'static str>
+    where
+        T: StateTypes,
+    {
+        T::type_names()
+    }
+
+    /// Transforms the loaded templates before anything is rendered
+    ///
+    /// `transform` gets mutable access to the template set and can patch,
+    /// rename, add or remove templates. The output filesystem is reset to the
+    /// transformed templates, so call this before running the app.
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - A function editing the loaded templates
+    ///
+    /// # Returns
+    ///
+    /// The App instance with its templates transformed
+    pub fn transform_templates<F: FnOnce(&mut MemFS)>(mut self, transform: F) -> Self {
+        let templates = {
+            let mut templates = self.templates_mut();
+            transform(&mut templates);
+            templates.clone()
+        };
+        self.output.make_mut().fs = templates;
+        self.engine_mut().clear_cache();
+        self
+    }
+
+    /// Sets the clock used to timestamp files in the app's filesystems
+    ///
+    /// Use a fixed clock, such as [`Clock::source_date_epoch`], for
+    /// reproducible output.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The clock to read timestamps from
+    ///
+    /// # Returns
+    ///
+    /// The App instance using the clock
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.templates_mut().set_clock(clock.clone());
+        self.output.make_mut().fs.set_clock(clock);
+        self
+    }
+
+    /// Returns the content of a file in the output MemFS
+    ///
+    /// After a run this is the rendered output, so it can be inspected
+    /// without writing it to disk first.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file in the output
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<u8>>` - The file's raw content, or an error if the file
+    ///   does not exist
+    pub async fn read_output(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(self.output.get().fs.read_file(path)?.clone())
+    }
+
+    /// Lists the contents of a directory of the output MemFS
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The path of the directory in the output, or `""` for the root
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<String>>` - The names of the files and directories it
+    ///   contains, sorted, or an error if it is not a directory
+    pub async fn list_output(&self, dir: &str) -> Result<Vec<String>> {
+        let mut names = self.output.get().fs.list_dir(dir)?;
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the source of a loaded template
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path of the template in the loaded template set
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String>` - The template source, or an error if the template
+    ///   does not exist or is not valid UTF-8
+    pub fn get_template_source(&self, template_path: &str) -> Result<String> {
+        Ok(self.templates().read_file_string(template_path)?)
+    }
+
+    /// Replaces (or adds) the source of a template
+    ///
+    /// The change is visible to all subsequent renders: any compiled copy of
+    /// the template is discarded. The file is also updated in the output
+    /// filesystem so unrendered templates are copied through with the new
+    /// source. Clones of the app keep the previous source.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path of the template in the loaded template set
+    /// * `source` - The new template source
+    pub fn set_template_source(
+        &mut self,
+        template_path: &str,
+        source: impl Into<String>,
+    ) -> Result<()> {
+        let content = source.into().into_bytes();
+        self.templates_mut()
+            .write_file(template_path, content.clone())?;
+        self.output
+            .make_mut()
+            .fs
+            .write_file(template_path, content)?;
+        self.engine_mut().clear_cache();
+        Ok(())
+    }
+
+    /// Loads the templates in a directory into the app
+    ///
+    /// The templates are merged into those already loaded, replacing
+    /// templates at the same paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_dir` - Path to the directory containing templates
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the templates loaded
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be read, or if a template would
+    /// replace a directory or the other way around
+    pub fn with_templates<P: AsRef<Path>>(mut self, template_dir: P) -> Self {
+        let template_dir = template_dir.as_ref();
+        let loaded = MemFS::read_from_disk(template_dir).and_then(|templates| {
+            self.templates_mut().merge(templates.clone(), true)?;
+            self.output.make_mut().fs.merge(templates, true)
+        });
+        if let Err(err) = loaded {
+            panic!(
+                "failed to load templates from {}: {err}",
+                template_dir.display()
+            );
+        }
+        self.engine_mut().clear_cache();
+        self
+    }
+
+    /// Adds a template from an in-memory string
+    ///
+    /// The template is available to render operations under `name`, exactly
+    /// as if it had been loaded from the template directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The path the template is registered under
+    /// * `content` - The template source
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid template path
+    pub fn with_template_string(mut self, name: &str, content: String) -> Self {
+        if let Err(err) = self.set_template_source(name, content) {
+            panic!("failed to add template '{name}': {err}");
+        }
+        self
+    }
+
+    /// Renders a template source against a context, without an operation
+    ///
+    /// The source is compiled with the app's engine configuration, including
+    /// filters, syntax and undefined behavior, and rendered against
+    /// `context` merged into the shared context, exactly as an operation's
+    /// output would be. It can include templates of the app, but is neither
+    /// registered as a template nor written to the MemFS.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The template source to render
+    /// * `context` - The context to render it against
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String>` - The rendered output, or an error if the source
+    ///   cannot be compiled or rendered
+    pub fn render_str<C: Serialize>(&self, source: &str, context: &C) -> Result<String> {
+        let context = self.render_context(Value::from_serialize(context));
+        let scope = RenderScope {
+            named_outputs: Arc::new(RwLock::new(self.output.get().named_outputs.clone())),
+            translations: self.translations.clone(),
+            locale: self.locale.clone(),
+        };
+        Ok(scope.enter(|| self.engine().render_str(source, &context))?)
+    }
+
+    /// Removes every registered operation so another set can be registered
+    ///
+    /// The state, the templates, the MemFS with everything rendered so far
+    /// and the engine configuration are kept, so an app can run several
+    /// phases without reloading its templates. Metadata registered with
+    /// operations is removed along with them.
+    pub fn clear_operations(&mut self) {
+        self.operations.clear();
+        self.nodes.clear();
+        self.metadata.clear();
+    }
+
+    /// Declares the last registered operation as a node of a dependency graph
+    ///
+    /// The operation is given a name other nodes can depend on, and the
+    /// names of the nodes it depends on. Dependencies only affect
+    /// [`App::run_graph`]; other ways of running the app keep registration
+    /// order. Node names are unrelated to the names of named operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the node
+    /// * `dependencies` - The names of the nodes that must run first
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the node declared
+    ///
+    /// # Panics
+    ///
+    /// Panics if no operation has been registered, or if another node
+    /// already has the same name
+    pub fn node(mut self, name: &str, dependencies: &[&str]) -> Self {
+        let Some(index) = self.operations.len().checked_sub(1) else {
+            panic!("node '{name}' declared before any operation was registered");
+        };
+        if self.nodes.values().any(|node| node.name == name) {
+            panic!("a node called '{name}' was already declared");
+        }
+        let node = graph::Node {
+            name: name.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        };
+        self.nodes.insert(index, node);
+        self
+    }
+
+    /// Starts a run from a copy of the app's output
+    pub(crate) fn begin_run(&self) -> Run {
+        Run::new(&self.output.get())
+    }
+
+    /// Makes what a run rendered the app's output
+    ///
+    /// # Returns
+    ///
+    /// The app's new output
+    pub(crate) fn finish_run(&self, run: Run) -> Arc<Output> {
+        let output = Arc::new(run.into_output());
+        self.output.set(output.clone());
+        output
+    }
+
+    /// Returns the template engine
+    fn engine(&self) -> &TemplateEngine<'static> {
+        &self.templates.engine
+    }
+
+    /// Returns the template engine for configuring
+    fn engine_mut(&mut self) -> &mut TemplateEngine<'static> {
+        &mut self.template_set_mut().engine
+    }
+
+    /// Returns the templates and engine for editing
+    ///
+    /// If clones of the app still share them, the app is first given a copy
+    /// of its own, so the edit doesn't affect the clones.
+    fn template_set_mut(&mut self) -> &mut TemplateSet {
+        if Arc::get_mut(&mut self.templates).is_none() {
+            self.templates = Arc::new(self.templates.deep_clone());
+        }
+        Arc::get_mut(&mut self.templates).expect("the template set was just copied")
+    }
+
+    /// Acquires a read lock on the template filesystem
+    fn templates(&self) -> RwLockReadGuard<'_, MemFS> {
+        self.templates
+            .fs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquires a write lock on the template filesystem
+    fn templates_mut(&mut self) -> RwLockWriteGuard<'_, MemFS> {
+        self.template_set_mut()
+            .fs
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns what template functions of a run's renders read
+    fn scope(&self, run: &Run) -> RenderScope {
+        RenderScope {
+            named_outputs: run.named_outputs.clone(),
+            translations: self.translations.clone(),
+            locale: self.locale.clone(),
+        }
+    }
+
+    /// Renders a template, enforcing the output size limit if one is set
+    fn render_template(&self, run: &Run, template_path: &str, context: &Value) -> Result<String> {
+        let scope = self.scope(run);
+        let Some(limit) = self.output_size_limit else {
+            return Ok(scope.enter(|| self.engine().render(template_path, context))?);
+        };
+
+        let mut writer = LimitedWriter::new(limit);
+        let result = scope.enter(|| {
+            self.engine()
+                .render_to_write(template_path, context, &mut writer)
+        });
+        match result {
+            Ok(()) => Ok(writer.into_string()),
+            Err(_) if writer.exceeded() => Err(Error::OutputTooLarge {
+                template: template_path.to_string(),
+                limit,
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Collects what extractor parameters of an operation can receive
+    fn extractors(&self, run: &Run) -> Extractors {
+        Extractors {
+            fs: run.fs.clone(),
+            template_path: None,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Builds the final template context for an operation's output
+    fn render_context(&self, output: Value) -> Value {
+        match &self.shared_context {
+            Some(shared) => merge_values(shared, &output),
+            None => output,
+        }
+    }
+}
+
+impl<T: Send + Sync + Clone + 'static> App<T> {
+    /// Registers a render operation with the application
+    ///
+    /// # Type Parameters
+    ///
+    /// * `FSig` - The function signature of the operation
+    /// * `F` - The operation type
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_operation<FSig, F>(self, template_path: &str, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        self.render_operation_to(template_path, template_path, operation)
+    }
+
+    /// Registers a render operation writing to a different path than its template
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `output_path` - The path the rendered output is written to
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_operation_to<FSig, F>(
+        mut self,
+        template_path: &str,
+        output_path: &str,
+        operation: F,
+    ) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::Render {
+            template_path: template_path.to_string(),
+            output_path: output_path.to_string(),
+            op,
+        });
+        self
+    }
+
+    /// Registers a render operation that fails if it takes too long
+    ///
+    /// If the operation has not finished after `duration`, its future is
+    /// dropped, and `run` stops with an [`Error::OperationTimeout`] as the
+    /// source of the [`Error::OperationFailed`] it returns. Dropping the
+    /// future cancels the operation at its current `.await`, so operations
+    /// should not leave state half-updated across an `.await`.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `duration` - How long the operation may run
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_operation_with_timeout<FSig, F>(
+        mut self,
+        template_path: &str,
+        duration: Duration,
+        operation: F,
+    ) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        let label = template_path.to_string();
+        let timed: SharedRenderOperation = Arc::new(move |extractors: &Extractors| {
+            let fut = op(extractors);
+            let label = label.clone();
+            Box::pin(async move {
+                tokio::time::timeout(duration, fut)
+                    .await
+                    .map_err(|_| Error::OperationTimeout { label, duration })?
+            })
+        });
+        self.operations.push(OperationKind::Render {
+            template_path: template_path.to_string(),
+            output_path: template_path.to_string(),
+            op: timed,
+        });
+        self
+    }
+
+    /// Registers a fallible render operation that is retried when it fails
+    ///
+    /// Each time the operation returns an error, it is invoked again with
+    /// freshly extracted parameters after the policy's backoff, until it
+    /// succeeds or has run [`RetryPolicy::max_attempts`] times. If every
+    /// attempt fails, `run` stops with an [`Error::RetriesExhausted`],
+    /// carrying the attempt count and the last error, as the source of the
+    /// [`Error::OperationFailed`] it returns.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `policy` - How often to retry and how long to wait in between
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_operation_with_retry<FSig, F, Out, E>(
+        mut self,
+        template_path: &str,
+        policy: RetryPolicy,
+        operation: F,
+    ) -> Self
+    where
+        FSig: FunctionSignature<Output = std::result::Result<Out, E>> + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        Out: Serialize + 'static,
+        E: Into<Error>,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_try_render_operation(operation);
+        let retried: SharedRenderOperation = Arc::new(move |extractors: &Extractors| {
+            let (op, extractors) = (op.clone(), extractors.clone());
+            Box::pin(async move {
+                let mut attempt = 1;
+                loop {
+                    let err = match op(&extractors).await {
+                        Ok(output) => return Ok(output),
+                        Err(err) => err,
+                    };
+                    if attempt >= policy.max_attempts() {
+                        return Err(Error::RetriesExhausted {
+                            attempts: attempt,
+                            source: Box::new(err),
+                        });
+                    }
+                    log::debug!("attempt {attempt} failed, retrying: {err}");
+                    tokio::time::sleep(policy.delay(attempt)).await;
+                    attempt += 1;
+                }
+            })
+        });
+        self.operations.push(OperationKind::Render {
+            template_path: template_path.to_string(),
+            output_path: template_path.to_string(),
+            op: retried,
+        });
+        self
+    }
+
+    /// Registers a render operation for every template matching a glob pattern
+    ///
+    /// The pattern is expanded against the templates loaded when this is
+    /// called, and the operation is invoked once per matching template, in
+    /// path order. Each output is written to the path of its template. The
+    /// operation can take a [`TemplatePath`](state::TemplatePath) parameter to learn which
+    /// template it is rendering. `*` does not match `/`; use `**` to match
+    /// across directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The glob pattern template paths are matched against
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with one render operation registered per match
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid glob pattern
+    pub fn render_glob<FSig, F>(mut self, pattern: &str, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let glob = glob::Pattern::new(pattern)
+            .unwrap_or_else(|err| panic!("invalid glob pattern {pattern:?}: {err}"));
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let matches: Vec<String> = self
+            .templates()
+            .walk()
+            .into_iter()
+            .filter(|path| glob.matches_with(path, options))
+            .collect();
+        if matches.is_empty() {
+            log::warn!("glob pattern {pattern:?} matches no templates");
+        }
+
+        let op = self.share_render_operation(operation);
+        for template_path in matches {
+            self.operations.push(OperationKind::Render {
+                output_path: template_path.clone(),
+                template_path,
+                op: op.clone(),
+            });
+        }
+        self
+    }
+
+    /// Registers a render operation that can fail
+    ///
+    /// If the operation returns an error, `run` stops before any later
+    /// operation and returns [`Error::OperationFailed`] carrying the
+    /// operation's index and its error.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn try_render_operation<FSig, F, Out, E>(
+        mut self,
+        template_path: &str,
+        operation: F,
+    ) -> Self
+    where
+        FSig: FunctionSignature<Output = std::result::Result<Out, E>> + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        Out: Serialize + 'static,
+        E: Into<Error>,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_try_render_operation(operation);
+        self.operations.push(OperationKind::Render {
+            template_path: template_path.to_string(),
+            output_path: template_path.to_string(),
+            op,
+        });
+        self
+    }
+
+    /// Registers a render operation whose output carries metadata
+    ///
+    /// The rendered template is written to `output_path`, and after `run` a
+    /// manifest is written to [`META_MANIFEST_PATH`] at the root of the output
+    /// directory. The manifest is a JSON object of the form:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "files": {
+    ///     "<output path>": <metadata>
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Entries are keyed by output path and sorted. Registering metadata for
+    /// the same output path twice keeps the last value.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The path to the template file
+    /// * `output_path` - The path the rendered output is written to
+    /// * `meta` - Arbitrary metadata recorded for the output
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_operation_with_meta<FSig, F>(
+        self,
+        template_path: &str,
+        output_path: &str,
+        meta: serde_json::Value,
+        operation: F,
+    ) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let mut app = self.render_operation_to(template_path, output_path, operation);
+        app.metadata.insert(output_path.to_string(), meta);
+        app
+    }
+
+    /// Registers an operation whose output is written as JSON
+    ///
+    /// No template is involved: the operation's output is serialized with
+    /// `serde_json::to_string_pretty` and written to `output_path`. This is
+    /// useful for emitting intermediate artifacts alongside rendered files.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_path` - The path the JSON is written to
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn json_operation<FSig, F>(mut self, output_path: &str, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::Serialized {
+            output_path: output_path.to_string(),
+            format: OutputFormat::Json,
+            op,
+        });
+        self
+    }
+
+    /// Registers an operation whose output is written as YAML
+    ///
+    /// Works like [`App::json_operation`], serializing the operation's output
+    /// with `serde_yaml` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_path` - The path the YAML is written to
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    #[cfg(feature = "yaml")]
+    pub fn yaml_operation<FSig, F>(mut self, output_path: &str, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::Serialized {
+            output_path: output_path.to_string(),
+            format: OutputFormat::Yaml,
+            op,
+        });
+        self
+    }
+
+    /// Registers an operation whose output is stored under a name
+    ///
+    /// The output is not rendered by itself. Instead, templates can declare it
+    /// as their context with a `{# quickform: context=<name> #}` directive and
+    /// be connected to it by [`App::auto_wire`]. Any template rendered after
+    /// the operation ran can also read the output with `{{ ref("<name>") }}`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the operation's output is stored under
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn named_operation<FSig, F>(mut self, name: &str, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::Named {
+            name: name.to_string(),
+            op,
+        });
+        self
+    }
+
+    /// Registers a render operation with a chain of fallback templates
+    ///
+    /// When the operation runs, the first candidate present in the loaded
+    /// templates is rendered, which implements project overrides of default
+    /// templates. If none exist, running fails with
+    /// [`Error::NoTemplateAvailable`] listing every candidate tried.
+    ///
+    /// # Arguments
+    ///
+    /// * `candidates` - Template paths to try, in order of preference
+    /// * `output_path` - Path the rendered output is written to
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_first_available<FSig, F>(
+        mut self,
+        candidates: &[&str],
+        output_path: &str,
+        operation: F,
+    ) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::FirstAvailable {
+            candidates: candidates.iter().map(|path| path.to_string()).collect(),
+            output_path: output_path.to_string(),
+            op,
+        });
+        self
+    }
+
+    /// Registers an operation whose output renders several templates into one file
+    ///
+    /// Each template is rendered against the operation's output and the
+    /// results are joined, in the order given, into a single document written
+    /// to `output_path`. Pass a plain string to only separate the sections, or
+    /// a [`Separator`] with a header to also label each section.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_path` - Path the combined document is written to
+    /// * `template_paths` - Templates rendered as the sections of the document
+    /// * `operation` - The operation function to register
+    /// * `separator` - How consecutive sections are joined
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_concatenated<FSig, F>(
+        mut self,
+        output_path: &str,
+        template_paths: &[&str],
+        operation: F,
+        separator: impl Into<Separator>,
+    ) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::Concatenated {
+            templates: template_paths.iter().map(|path| path.to_string()).collect(),
+            output_path: output_path.to_string(),
+            separator: separator.into(),
+            op,
+        });
+        self
+    }
+
+    /// Registers an operation whose output renders several related templates
+    ///
+    /// The operation runs once and each template is rendered against its
+    /// output. The bundle is atomic: outputs are only written once every
+    /// template has rendered successfully, so a failure never leaves a
+    /// partial set of files behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation function to register
+    /// * `outputs` - `(template_path, output_path)` pairs to render
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_bundle<FSig, F>(mut self, operation: F, outputs: &[(&str, &str)]) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let op = self.share_render_operation(operation);
+        self.operations.push(OperationKind::Bundle {
+            outputs: outputs
+                .iter()
+                .map(|(template, output)| (template.to_string(), output.to_string()))
+                .collect(),
+            op,
+        });
+        self
+    }
+
+    /// Registers an operation whose output renders several templates, each
+    /// to its own path
+    ///
+    /// Like [`App::render_bundle`] with every template rendered to the path
+    /// it was loaded from: the operation runs once per run, however many
+    /// templates use its output.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_paths` - Paths of the templates to render
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_operation_multi<FSig, F>(self, template_paths: &[&str], operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let outputs: Vec<_> = template_paths.iter().map(|path| (*path, *path)).collect();
+        self.render_bundle(operation, &outputs)
+    }
+
+    /// Renders a template once for every item of a collection state
+    ///
+    /// The collection added with `with_collection_state::<S>` is locked once
+    /// per run and each item is rendered as the whole context of its own
+    /// file, at the path returned by `path_fn`. If no collection of `S` was
+    /// added, running the app fails with an [`Error::OperationFailed`]
+    /// caused by [`Error::MissingCollection`].
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - Path to the template rendered for each item
+    /// * `path_fn` - Computes the output path of an item
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn render_per_item<S, P>(mut self, template_path: &str, path_fn: P) -> Self
+    where
+        S: Serialize + Send + Sync + 'static,
+        P: Fn(&S) -> String + Send + Sync + 'static,
+    {
+        let collection = self
+            .collections
+            .get(&TypeId::of::<S>())
+            .and_then(|collection| collection.clone().downcast::<Data<Vec<S>>>().ok());
+        let path_fn = Arc::new(path_fn);
+
+        let items = move || {
+            let collection = collection.clone();
+            let path_fn = path_fn.clone();
+            Box::pin(async move {
+                let Some(collection) = collection else {
+                    return Err(Error::MissingCollection(
+                        std::any::type_name::<S>().to_string(),
+                    ));
+                };
+                let items = collection.lock().await;
+                Ok(items
+                    .iter()
+                    .map(|item| (path_fn(item), Value::from_serialize(item)))
+                    .collect())
+            }) as Pin<Box<dyn Future<Output = Result<Vec<(String, Value)>>> + Send>>
+        };
+
+        self.operations.push(OperationKind::PerItem {
+            template_path: template_path.to_string(),
+            items: Arc::new(items),
+        });
+        self
+    }
+
+    /// Connects templates to named operations according to their directives
+    ///
+    /// Every loaded template declaring `{# quickform: context=<name> #}` gets
+    /// a render operation using the output of the named operation registered
+    /// under `<name>`, written to the template's own path. Named operations
+    /// must be registered before calling this, and templates naming an
+    /// unknown operation are skipped with a warning.
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the wired render operations registered
+    pub fn auto_wire(mut self) -> Self {
+        let names: Vec<&String> = self
+            .operations
+            .iter()
+            .filter_map(|operation| match operation {
+                OperationKind::Named { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        let mut wired = Vec::new();
+        {
+            let templates = self.templates();
+            for template_path in templates.walk() {
+                let source = match templates.read_file(&template_path) {
+                    Ok(content) => String::from_utf8_lossy(content),
+                    Err(_) => continue,
+                };
+                let Some(name) = parse_directives(&template_path, &source).context else {
+                    continue;
+                };
+                if names.contains(&&name) {
+                    wired.push(OperationKind::Wired {
+                        template_path,
+                        name,
+                    });
+                } else {
+                    log::warn!(
+                        "Template {} declares context `{}` but no named operation has that name",
+                        template_path,
+                        name
+                    );
+                }
+            }
+        }
+
+        self.operations.extend(wired);
+        self
+    }
+
+    /// Wraps an operation so it can be invoked with the app's state for rendering
+    fn share_render_operation<FSig, F>(&self, operation: F) -> SharedRenderOperation
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Serialize,
+        T: IntoFunctionParams<FSig>,
+    {
+        let state = self.state.clone();
+        Arc::new(move |extractors: &Extractors| {
+            let params = state.clone().into_params(extractors);
+            let fut = params.map(|params| operation.invoke(params));
+            Box::pin(async move {
+                let result = fut?.await;
+                Ok(Box::new(result) as Box<dyn Context>)
+            }) as Pin<Box<dyn Future<Output = _> + Send>>
+        })
+    }
+
+    /// Wraps a fallible operation so it can be invoked with the app's state
+    /// for rendering
+    fn share_try_render_operation<FSig, F, Out, E>(&self, operation: F) -> SharedRenderOperation
+    where
+        FSig: FunctionSignature<Output = std::result::Result<Out, E>> + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        Out: Serialize + 'static,
+        E: Into<Error>,
+        T: IntoFunctionParams<FSig>,
+    {
+        let state = self.state.clone();
+        Arc::new(move |extractors: &Extractors| {
+            let params = state.clone().into_params(extractors);
+            let fut = params.map(|params| operation.invoke(params));
+            Box::pin(async move {
+                match fut?.await {
+                    Ok(result) => Ok(Box::new(result) as Box<dyn Context>),
+                    Err(err) => Err(err.into()),
+                }
+            }) as Pin<Box<dyn Future<Output = _> + Send>>
+        })
+    }
+
+    /// Registers a state operation with the application
+    ///
+    /// # Type Parameters
+    ///
+    /// * `FSig` - The function signature of the operation
+    /// * `F` - The operation type
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn state_operation<FSig, F>(mut self, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Send + 'static,
+        T: IntoFunctionParams<FSig>,
+    {
+        let state = self.state.clone();
+        let wrapped_op = move |extractors: &Extractors| {
+            let params = state.clone().into_params(extractors);
+            let fut = params.map(|params| operation.invoke(params));
+            Box::pin(async move {
+                fut?.await;
+                Ok(())
+            }) as Pin<Box<dyn Future<Output = Result<()>> + Send>>
+        };
+
+        self.operations.push(OperationKind::State {
+            label: None,
+            op: Arc::new(wrapped_op),
+        });
+        self
+    }
+
+    /// Registers a state operation with a label used in diagnostics
+    ///
+    /// The label identifies the operation in errors and in the report of
+    /// [`App::run_timed`], which otherwise refer to state operations by index.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - A human-readable name for the operation
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn labeled_state_operation<FSig, F>(self, label: &str, operation: F) -> Self
+    where
+        FSig: FunctionSignature + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        FSig::Output: Send + 'static,
+        T: IntoFunctionParams<FSig>,
+    {
+        let mut app = self.state_operation(operation);
+        if let Some(OperationKind::State { label: slot, .. }) = app.operations.last_mut() {
+            *slot = Some(label.to_string());
+        }
+        app
+    }
+
+    /// Registers a state operation that also writes files
+    ///
+    /// The operation returns `(path, content)` pairs, such as an audit trail
+    /// of the changes it made, which are written to the MemFS and then to
+    /// disk along with rendered output. Their paths are subject to the
+    /// collision policy like any rendered output, but are only known once the
+    /// operation has run, so [`App::planned_output_paths`] lists the
+    /// operation as a single dynamic entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn emitting_state_operation<FSig, F>(mut self, operation: F) -> Self
+    where
+        FSig: FunctionSignature<Output = Vec<EmittedFile>> + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        T: IntoFunctionParams<FSig>,
+    {
+        let state = self.state.clone();
+        let wrapped_op = move |extractors: &Extractors| {
+            let params = state.clone().into_params(extractors);
+            let fut = params.map(|params| operation.invoke(params));
+            Box::pin(async move { Ok(fut?.await) })
+                as Pin<Box<dyn Future<Output = Result<Vec<EmittedFile>>> + Send>>
+        };
+
+        self.operations.push(OperationKind::Emitting {
+            op: Arc::new(wrapped_op),
+        });
+        self
+    }
+
+    /// Registers a state operation that can fail
+    ///
+    /// If the operation returns an error, `run` stops before any later
+    /// operation and returns [`Error::OperationFailed`] carrying the
+    /// operation's index and its error.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation function to register
+    ///
+    /// # Returns
+    ///
+    /// The App instance with the new operation registered
+    pub fn try_state_operation<FSig, F, E>(mut self, operation: F) -> Self
+    where
+        FSig: FunctionSignature<Output = std::result::Result<(), E>> + 'static,
+        F: Operation<FSig> + Send + Sync + 'static,
+        F::Future: Send + 'static,
+        E: Into<Error>,
+        T: IntoFunctionParams<FSig>,
+    {
+        let state = self.state.clone();
+        let wrapped_op = move |extractors: &Extractors| {
+            let params = state.clone().into_params(extractors);
+            let fut = params.map(|params| operation.invoke(params));
+            Box::pin(async move { fut?.await.map_err(Into::into) })
+                as Pin<Box<dyn Future<Output = Result<()>> + Send>>
+        };
+
+        self.operations.push(OperationKind::State {
+            label: None,
+            op: Arc::new(wrapped_op),
+        });
+        self
+    }
+
+    /// Executes all registered operations and renders their results
+    ///
+    /// Every file loaded with the templates that no operation renders, such
+    /// as images and other binary assets, is copied to `output_dir` with its
+    /// bytes unchanged. Such files are never parsed as templates.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success or an error if any operation fails
+    pub async fn run<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
+        let run = self.begin_run();
+        let result = self.execute_all(&run).await;
+        let result = result.and_then(|_| self.write_output(&run, output_dir));
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations and writes the output atomically
+    ///
+    /// Operations run exactly as with `run`, but the output is written to a
+    /// staging directory next to `output_dir` that then replaces it, so
+    /// `output_dir` is either fully updated or left untouched, even if
+    /// writing fails partway through.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success or an error if any operation or the write fails
+    pub async fn run_atomic<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
+        let run = self.begin_run();
+        let result = self.execute_all(&run).await.and_then(|_| {
+            self.write_manifest(&run)?;
+            Ok(run.fs_mut().write_to_disk_atomic(output_dir.as_ref())?)
+        });
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations, leaving the MemFS untouched if any
+    /// of them fails
+    ///
+    /// Operations run exactly as with `run`, but what they render only
+    /// replaces the app's output once every operation has succeeded, so no
+    /// half-generated files are left in the MemFS. As with `run`, nothing is
+    /// written to disk until every operation has succeeded. State changes
+    /// made by operations that ran before the failure are not rolled back.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success or an error if any operation fails
+    pub async fn run_transactional<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
+        let run = self.begin_run();
+        self.execute_all(&run).await?;
+        let result = self.write_output(&run, output_dir);
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations until `token` is cancelled, and
+    /// writes the results to disk
+    ///
+    /// Operations run in order exactly as with `run`. Once the token is
+    /// cancelled, no further operation starts and the running operation's
+    /// future is dropped at its current `.await`, so operations should not
+    /// leave state half-updated across an `.await`. A cancelled run writes
+    /// nothing to disk; output rendered before the cancellation stays in the
+    /// MemFS.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    /// * `token` - Cancels the run, e.g. when a client disconnects
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success, [`Error::Cancelled`] if the token was
+    ///   cancelled before every operation finished, or an error if any
+    ///   operation fails
+    pub async fn run_with_cancellation<P: AsRef<Path>>(
+        &self,
+        output_dir: P,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let run = self.begin_run();
+        let result = async {
+            for (index, operation) in self.operations.iter().enumerate() {
+                tokio::select! {
+                    biased;
+                    _ = token.cancelled() => return Err(Error::Cancelled),
+                    result = self.execute(&run, index, operation) => {
+                        result?;
+                    }
+                }
+            }
+            self.write_output(&run, output_dir)
+        }
+        .await;
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations, measuring how long each takes
+    ///
+    /// Operations run and output is written exactly as with `run`. Each
+    /// duration covers the operation itself and the rendering of its output.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RunReport>` - The duration of every operation, or an error if
+    ///   any operation fails
+    pub async fn run_timed<P: AsRef<Path>>(&self, output_dir: P) -> Result<RunReport> {
+        let run = self.begin_run();
+        let result = async {
+            let mut report = RunReport::default();
+            for (index, operation) in self.operations.iter().enumerate() {
+                let start = Instant::now();
+                self.execute(&run, index, operation).await?;
+                report
+                    .timings
+                    .push((operation.label(index), start.elapsed()));
+            }
+            self.write_output(&run, output_dir)?;
+            Ok(report)
+        }
+        .await;
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations and writes only the files that
+    /// changed to disk
+    ///
+    /// Operations run exactly as with `run`, but each file is compared with
+    /// the one already in `output_dir` and skipped if the content is
+    /// identical, so unchanged files keep their modification time.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<WriteSummary>` - How many files were written and skipped, or
+    ///   an error if any operation fails
+    pub async fn run_incremental<P: AsRef<Path>>(&self, output_dir: P) -> Result<WriteSummary> {
+        let run = self.begin_run();
+        let result = self.execute_all(&run).await.and_then(|_| {
+            self.write_manifest(&run)?;
+            Ok(run.fs_mut().write_changed_to_disk(output_dir.as_ref())?)
+        });
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations and makes `output_dir` mirror the
+    /// MemFS
+    ///
+    /// Operations run exactly as with `run`, but files in `output_dir` that
+    /// the MemFS doesn't contain, such as outputs of templates that were
+    /// removed, are deleted before the rendered files are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<String>>` - The paths, relative to `output_dir`, of the
+    ///   files that were deleted, or an error if any operation fails
+    pub async fn run_synced<P: AsRef<Path>>(&self, output_dir: P) -> Result<Vec<String>> {
+        let run = self.begin_run();
+        let result = self.execute_all(&run).await.and_then(|_| {
+            self.write_manifest(&run)?;
+            Ok(run
+                .fs_mut()
+                .write_to_disk_synced(output_dir.as_ref(), true)?)
+        });
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations without writing anything to disk
+    ///
+    /// Operations run in order exactly as with `run`, and the MemFS is
+    /// populated, but the rendered files are returned instead of written out.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<HashMap<String, String>>` - Rendered content keyed by output
+    ///   path, or an error if any operation fails
+    pub async fn run_in_memory(&self) -> Result<HashMap<String, String>> {
+        let run = self.begin_run();
+        let result = self.execute_all(&run).await;
+        self.finish_run(run);
+        Ok(result?
+            .into_iter()
+            .flat_map(StepResult::into_files)
+            .collect())
+    }
+
+    /// Executes all registered operations, yielding each file as it is rendered
+    ///
+    /// Operations run in order exactly as with `run`, one at a time as the
+    /// stream is polled. Each rendered `(path, content)` pair is yielded as
+    /// soon as its operation finishes; state operations run in between
+    /// without yielding anything. Rendered files are also written to the
+    /// MemFS but not to disk, so consumers can report progress and write
+    /// files themselves. No lock on the MemFS is held between items.
+    ///
+    /// The stream ends after the first error. What was rendered becomes the
+    /// app's output once the stream ends.
+    ///
+    /// # Returns
+    ///
+    /// A stream of the rendered files, in the order they were rendered
+    pub fn run_stream(&self) -> impl Stream<Item = Result<(String, String)>> + '_ {
+        let pending = VecDeque::new();
+        let state = (0, None, pending);
+        stream::unfold(state, move |(mut next, mut run, mut pending)| async move {
+            loop {
+                if let Some(file) = pending.pop_front() {
+                    return Some((Ok(file), (next, run, pending)));
+                }
+                let index = next;
+                let Some(operation) = self.operations.get(index) else {
+                    if let Some(run) = run.take() {
+                        self.finish_run(run);
+                    }
+                    return None;
+                };
+                next += 1;
+                let current = run.get_or_insert_with(|| self.begin_run());
+                match self.execute(current, index, operation).await {
+                    Ok(step) => pending.extend(step.into_files()),
+                    Err(err) => {
+                        if let Some(run) = run.take() {
+                            self.finish_run(run);
+                        }
+                        return Some((Err(err), (self.operations.len(), None, pending)));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Executes all registered operations, running render operations
+    /// concurrently, and writes the results to disk
+    ///
+    /// State operations, and every operation other than a plain render
+    /// operation, run in registration order: each waits for everything
+    /// registered before it and finishes before anything registered after it
+    /// starts. Consecutive render operations between them run concurrently,
+    /// so their contexts may be produced in any order. With
+    /// [`CollisionPolicy::Rename`], which of two colliding concurrent renders
+    /// keeps the requested path is unspecified.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success or an error if any operation fails
+    pub async fn run_parallel<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
+        let run = self.begin_run();
+        let result = async {
+            let mut index = 0;
+            for group in self
+                .operations
+                .chunk_by(|a, b| a.is_render() && b.is_render())
+            {
+                let executions = group
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, operation)| self.execute(&run, index + offset, operation));
+                for result in futures::future::join_all(executions).await {
+                    result?;
+                }
+                index += group.len();
+            }
+            self.write_output(&run, output_dir)
+        }
+        .await;
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes all registered operations in dependency order and writes
+    /// the results to disk
+    ///
+    /// Each operation declared with [`App::node`] runs once every node it
+    /// depends on has finished. Operations whose dependencies have all
+    /// finished run concurrently, whatever their kind, so operations not
+    /// declared as nodes, which have no dependencies, all run concurrently
+    /// at the start. Nothing runs if a dependency is unknown or the
+    /// dependencies form a cycle.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the rendered files to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success, [`Error::UnknownDependency`] or
+    ///   [`Error::DependencyCycle`] if the graph is invalid, or an error if
+    ///   any operation fails
+    pub async fn run_graph<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
+        let waves = graph::waves(self.operations.len(), &self.nodes, |index| {
+            match self.nodes.get(&index) {
+                Some(node) => node.name.clone(),
+                None => self.operations[index].label(index),
+            }
+        })?;
+        let run = self.begin_run();
+        let result = async {
+            for wave in waves {
+                let executions = wave
+                    .into_iter()
+                    .map(|index| self.execute(&run, index, &self.operations[index]));
+                for result in futures::future::join_all(executions).await {
+                    result?;
+                }
+            }
+            self.write_output(&run, output_dir)
+        }
+        .await;
+        self.finish_run(run);
+        result
+    }
+
+    /// Executes every registered operation in order as part of `run`
+    async fn execute_all(&self, run: &Run) -> Result<Vec<StepResult>> {
+        let mut results = Vec::with_capacity(self.operations.len());
+        for (index, operation) in self.operations.iter().enumerate() {
+            results.push(self.execute(run, index, operation).await?);
+        }
+        Ok(results)
+    }
+
+    /// Executes a single render operation and returns its rendered output
+    ///
+    /// Only the render operation registered for `template_path` runs: state
+    /// operations and other renders are skipped, and nothing is written to
+    /// the MemFS or to disk. If several operations render the template, the
+    /// last registered one is used, as its output is the one `run` keeps.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_path` - The template of the render operation to execute
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String>` - The rendered output, or
+    ///   [`Error::NoRenderOperation`] if no operation renders the template
+    pub async fn render_one(&self, template_path: &str) -> Result<String> {
+        let (index, operation) = self
+            .operations
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, operation)| {
+                matches!(operation, OperationKind::Render { template_path: path, .. }
+                    if path == template_path)
+            })
+            .ok_or_else(|| Error::NoRenderOperation(template_path.to_string()))?;
+        let run = self.begin_run();
+        let jobs = self.prepare(&run, index, operation).await?;
+        self.render_job(&run, index, &jobs[0])
+    }
+
+    /// Returns the paths the registered operations will render to
+    ///
+    /// Nothing is executed; paths come from how operations were registered,
+    /// in execution order. Outputs renamed by [`CollisionPolicy::Rename`]
+    /// are listed under their requested path.
+    ///
+    /// Per-item operations and emitting state operations only know their
+    /// paths once they run, so each is listed as a single
+    /// `<dynamic: label>` entry, where `label` is the operation's template
+    /// path or label.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<String>` - The output path of every render, or a dynamic
+    ///   marker, in order
+    pub fn planned_output_paths(&self) -> Vec<String> {
+        self.operations
+            .iter()
+            .enumerate()
+            .flat_map(|(index, operation)| match operation.dynamic_output(index) {
+                Some(marker) => vec![marker],
+                None => operation
+                    .output_paths()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Returns the number of registered operations, of every kind
+    pub fn operation_count(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns the output paths of the registered operations, borrowed
+    ///
+    /// Like [`App::planned_output_paths`], but without allocating a copy of
+    /// each path. Operations whose paths are only known once they run are
+    /// left out.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<&str>` - The output path of every render, in order
+    pub fn render_targets(&self) -> Vec<&str> {
+        self.operations
+            .iter()
+            .flat_map(OperationKind::output_paths)
+            .collect()
+    }
+
+    /// Returns the SHA-256 hash of every file in the MemFS
+    ///
+    /// Hashes are computed from the current content of the MemFS, so after
+    /// `run` they cover the rendered output as well as any template or
+    /// binary file copied through. They only depend on file content and are
+    /// stable across runs and platforms, so they can key a build cache.
+    ///
+    /// # Returns
+    ///
+    /// * `HashMap<String, String>` - The lowercase hex SHA-256 digest of
+    ///   every file, keyed by its path
+    #[cfg(feature = "hashes")]
+    pub fn content_hashes(&self) -> HashMap<String, String> {
+        use sha2::{Digest, Sha256};
+
+        let output = self.output.get();
+        let fs = &output.fs;
+        fs.walk()
+            .into_iter()
+            .filter_map(|path| {
+                let digest = Sha256::digest(fs.read_file(&path).ok()?);
+                let hex = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+                Some((path, hex))
+            })
+            .collect()
+    }
+
+    /// Runs the app in memory and compares the output with a golden directory
+    ///
+    /// Nothing is written to disk unless the `QUICKFORM_UPDATE_GOLDENS`
+    /// environment variable is set, in which case the golden directory is
+    /// replaced by the generated output and the comparison succeeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `golden_dir` - The directory holding the expected output
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Ok if the output matches, [`Error::GoldenMismatch`]
+    ///   with every difference found if it doesn't, or an error if an
+    ///   operation fails or the golden directory cannot be read or updated
+    pub async fn assert_matches_golden<P: AsRef<Path>>(&self, golden_dir: P) -> Result<()> {
+        let golden_dir = golden_dir.as_ref();
+        let run = self.begin_run();
+        let result = self.execute_all(&run).await;
+        let output = self.finish_run(run);
+        result?;
+        let output = &output.fs;
+
+        if golden::update_requested() {
+            if golden_dir.exists() {
+                if !golden_dir.is_dir() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotADirectory,
+                        format!("{} is not a directory", golden_dir.display()),
+                    )
+                    .into());
+                }
+                std::fs::remove_dir_all(golden_dir)?;
+            }
+            output.write_to_disk(golden_dir)?;
+            return Ok(());
+        }
+
+        let golden = MemFS::read_from_disk(golden_dir)?;
+        let diffs = golden::diff(&golden, output);
+        if diffs.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::GoldenMismatch(diffs))
+        }
+    }
+
+    /// Converts the app into a [`Runner`] that executes one operation at a time
+    ///
+    /// Useful for debuggers and interactive tools that need to inspect state
+    /// between operations instead of running the whole pipeline at once.
+    pub fn into_runner(self) -> Runner<T> {
+        Runner::new(self)
+    }
+
+    /// Executes a single operation, calling the operation hooks around it
+    pub(crate) async fn execute(
+        &self,
+        run: &Run,
+        index: usize,
+        operation: &OperationKind,
+    ) -> Result<StepResult> {
+        if self.start_hooks.is_empty() && self.complete_hooks.is_empty() {
+            return self.execute_operation(run, index, operation).await;
+        }
+        let label = operation.label(index);
+        for hook in &self.start_hooks {
+            hook(&label);
+        }
+        let start = Instant::now();
+        let result = self.execute_operation(run, index, operation).await;
+        let elapsed = start.elapsed();
+        for hook in &self.complete_hooks {
+            hook(&label, elapsed, result.as_ref().map(|_| ()));
+        }
+        result
+    }
+
+    /// Executes a single operation, writing rendered output to the run's MemFS
+    ///
+    /// Every file an operation renders is rendered before any is written, so
+    /// an operation either writes all of its outputs or none of them.
+    async fn execute_operation(
+        &self,
+        run: &Run,
+        index: usize,
+        operation: &OperationKind,
+    ) -> Result<StepResult> {
+        if let OperationKind::Emitting { op } = operation {
+            let mut files = op(&self.extractors(run))
+                .await
+                .map_err(|source| operation_failed(source, index, operation))?;
+            self.claim_paths(run, &mut files)?;
+            let mut fs = run.fs_mut();
+            for (path, content) in &files {
+                fs.write_file(path, content.clone())?;
+            }
+            return Ok(StepResult::Emitted { files });
+        }
+
+        let mut files = Vec::new();
+        if let OperationKind::Serialized { output_path, .. } = operation {
+            let content = self.render_serialized(run, index, operation).await?;
+            files.push((output_path.clone(), content));
+        }
+        for job in self.prepare(run, index, operation).await? {
+            files.push((
+                job.output_path.to_string(),
+                self.render_job(run, index, &job)?,
+            ));
+        }
+
+        if let OperationKind::Concatenated {
+            templates,
+            output_path,
+            separator,
+            ..
+        } = operation
+        {
+            let mut document = String::new();
+            for (index, (template_path, (_, section))) in templates.iter().zip(files).enumerate() {
+                document.push_str(&separator.prefix(index, template_path));
+                document.push_str(&section);
+            }
+            files = vec![(output_path.clone(), document)];
+        }
+
+        self.claim_paths(run, &mut files)?;
+
+        {
+            let mut fs = run.fs_mut();
+            for (path, content) in &files {
+                fs.write_file(path, content.clone().into_bytes())?;
+            }
+        }
+
+        Ok(match operation {
+            OperationKind::State { .. } => StepResult::State,
+            OperationKind::Emitting { .. } => unreachable!("emitting operations return early"),
+            OperationKind::Named { name, .. } => StepResult::Named { name: name.clone() },
+            OperationKind::Bundle { .. } => StepResult::Bundle { files },
+            OperationKind::PerItem { .. } => StepResult::Items { files },
+            OperationKind::Render { .. }
+            | OperationKind::Serialized { .. }
+            | OperationKind::Wired { .. }
+            | OperationKind::FirstAvailable { .. }
+            | OperationKind::Concatenated { .. } => {
+                let (path, content) = files.remove(0);
+                StepResult::Rendered { path, content }
+            }
+        })
+    }
+
+    /// Claims the paths of files about to be written during a run under the
+    /// collision policy, replacing each path with the one it may be written to
+    fn claim_paths<C>(&self, run: &Run, files: &mut [(String, C)]) -> Result<()> {
+        let paths = self.collision_policy.claim(
+            &mut run
+                .written_paths
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            files.iter().map(|(path, _)| path.as_str()),
+        )?;
+        for ((path, _), claimed) in files.iter_mut().zip(paths) {
+            *path = claimed;
+        }
+        Ok(())
+    }
+
+    /// Runs an operation's function, returning the renders it requires
+    ///
+    /// Operations that don't render anything return no jobs. Errors returned
+    /// by the function are attributed to the operation at `index`.
+    async fn prepare<'a>(
+        &self,
+        run: &Run,
+        index: usize,
+        operation: &'a OperationKind,
+    ) -> Result<Vec<RenderJob<'a>>> {
+        let failed = |source| operation_failed(source, index, operation);
+        let extractors = self.extractors(run);
+        let jobs = match operation {
+            OperationKind::Render {
+                template_path,
+                output_path,
+                op,
+            } => vec![RenderJob {
+                template_path,
+                output_path: Cow::Borrowed(output_path),
+                context: self.render_context(
+                    operation_output(op, &extractors.for_template(template_path))
+                        .await
+                        .map_err(failed)?,
+                ),
+            }],
+            OperationKind::FirstAvailable {
+                candidates,
+                output_path,
+                op,
+            } => {
+                let template_path = candidates
+                    .iter()
+                    .find(|candidate| self.templates().read_file(candidate).is_ok())
+                    .ok_or_else(|| Error::NoTemplateAvailable {
+                        tried: candidates.clone(),
+                    })?;
+                vec![RenderJob {
+                    template_path,
+                    output_path: Cow::Borrowed(output_path),
+                    context: self.render_context(
+                        operation_output(op, &extractors.for_template(template_path))
+                            .await
+                            .map_err(failed)?,
+                    ),
+                }]
+            }
+            OperationKind::Wired {
+                template_path,
+                name,
+            } => vec![RenderJob {
+                template_path,
+                output_path: Cow::Borrowed(template_path),
+                context: self.render_context(self.named_output(run, name)?),
+            }],
+            OperationKind::Bundle { outputs, op } => {
+                let context =
+                    self.render_context(operation_output(op, &extractors).await.map_err(failed)?);
+                outputs
+                    .iter()
+                    .map(|(template_path, output_path)| RenderJob {
+                        template_path,
+                        output_path: Cow::Borrowed(output_path),
+                        context: context.clone(),
+                    })
+                    .collect()
+            }
+            OperationKind::Concatenated {
+                templates,
+                output_path,
+                op,
+                ..
+            } => {
+                let context =
+                    self.render_context(operation_output(op, &extractors).await.map_err(failed)?);
+                templates
+                    .iter()
+                    .map(|template_path| RenderJob {
+                        template_path,
+                        output_path: Cow::Borrowed(output_path),
+                        context: context.clone(),
+                    })
+                    .collect()
+            }
+            OperationKind::PerItem {
+                template_path,
+                items,
+            } => items()
+                .await
+                .map_err(failed)?
+                .into_iter()
+                .map(|(output_path, item)| RenderJob {
+                    template_path,
+                    output_path: Cow::Owned(output_path),
+                    context: self.render_context(item),
+                })
+                .collect(),
+            OperationKind::State { op, .. } => {
+                op(&extractors).await.map_err(failed)?;
+                Vec::new()
+            }
+            // Serialized by `render_serialized` instead of a template
+            OperationKind::Serialized { .. } => Vec::new(),
+            // Run for its state changes only: `execute_operation` writes the
+            // files it returns itself
+            OperationKind::Emitting { op } => {
+                op(&extractors).await.map_err(failed)?;
+                Vec::new()
+            }
+            OperationKind::Named { name, op } => {
+                let output = operation_output(op, &extractors).await.map_err(failed)?;
+                run.named_outputs
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(name.clone(), output);
+                Vec::new()
+            }
+        };
+        Ok(jobs)
+    }
+
+    /// Runs a serialized operation's function and serializes its output
+    async fn render_serialized(
+        &self,
+        run: &Run,
+        index: usize,
+        operation: &OperationKind,
+    ) -> Result<String> {
+        let OperationKind::Serialized { format, op, .. } = operation else {
+            unreachable!("only serialized operations are serialized");
+        };
+        let output = op(&self.extractors(run))
+            .await
+            .map_err(|err| operation_failed(err, index, operation))?;
+        let content = match format {
+            OutputFormat::Json => output.to_json().map_err(std::io::Error::from)?,
+            #[cfg(feature = "yaml")]
+            OutputFormat::Yaml => output.to_yaml().map_err(std::io::Error::other)?,
+        };
+        Ok(content)
+    }
+
+    /// Renders a job's template and applies post-processing
+    ///
+    /// Template engine errors are attributed to the template and to the
+    /// operation at `index`.
+    fn render_job(&self, run: &Run, index: usize, job: &RenderJob<'_>) -> Result<String> {
+        let resolved = self.resolve_template(job.template_path);
+        let rendered = self
+            .render_template(run, &resolved, &job.context)
+            .map_err(|err| in_operation(err, &self.templates(), &resolved, index))?;
+        self.post_process(&job.output_path, rendered)
+    }
+
+    /// Applies the configured post-render steps to a rendered file
+    #[cfg_attr(not(feature = "markdown"), allow(unused_variables))]
+    fn post_process(&self, output_path: &str, rendered: String) -> Result<String> {
+        #[cfg(feature = "markdown")]
+        let rendered = match &self.markdown_transform {
+            Some(transform) if markdown::is_markdown(output_path) => {
+                markdown::transform(output_path, &rendered, transform)?
+            }
+            _ => rendered,
+        };
+        let rendered = if self.trim_trailing_whitespace {
+            trim_trailing_whitespace(&rendered)
+        } else {
+            rendered
+        };
+        self.transformers
+            .iter()
+            .try_fold(rendered, |content, transformer| {
+                transformer.transform(output_path, content)
+            })
+    }
+
+    /// Returns the output a named operation stored during a run
+    fn named_output(&self, run: &Run, name: &str) -> Result<Value> {
+        run.named_outputs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::MissingNamedOutput(name.to_string()))
+    }
+
+    /// Writes the metadata manifest and the whole MemFS of a run to disk
+    pub(crate) fn write_output<P: AsRef<Path>>(&self, run: &Run, output_dir: P) -> Result<()> {
+        self.write_manifest(run)?;
+        run.fs_mut().write_to_disk(output_dir.as_ref())?;
+        Ok(())
+    }
+
+    /// Writes the metadata manifest into a run's MemFS, if any output has
+    /// metadata
+    fn write_manifest(&self, run: &Run) -> Result<()> {
+        if !self.metadata.is_empty() {
+            let manifest = serde_json::json!({
+                "version": 1,
+                "files": self.metadata,
+            });
+            let manifest = serde_json::to_vec_pretty(&manifest).map_err(std::io::Error::from)?;
+            run.fs_mut().write_file(META_MANIFEST_PATH, manifest)?;
+        }
+        Ok(())
+    }
+
+    /// Executes all operations and streams the single render into a writer
+    ///
+    /// State operations run in registration order as in `run`, but the
+    /// rendered template is written directly to `writer` (stdout, a socket,
+    /// etc.) and the filesystem is never touched: files returned by emitting
+    /// state operations are discarded. Post-render transforms are not applied
+    /// to streamed output.
+    ///
+    /// How many files a per-item operation renders is only known once it
+    /// runs, so every operation runs before anything is written to `writer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination for the rendered output
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - Success, or [`Error::ExpectedSingleRender`] if the app
+    ///   does not have exactly one render operation
+    pub async fn render_to_writer<W: Write>(&self, mut writer: W) -> Result<()> {
+        let planned = self
+            .operations
+            .iter()
+            .map(|operation| operation.output_paths().len())
+            .sum();
+        let per_item = self
+            .operations
+            .iter()
+            .any(|operation| matches!(operation, OperationKind::PerItem { .. }));
+        if planned > 1 || (planned == 0 && !per_item) {
+            return Err(Error::ExpectedSingleRender { found: planned });
+        }
+
+        let run = self.begin_run();
+        let mut found = planned;
+        let mut serialized = None;
+        let mut render = None;
+        for (index, operation) in self.operations.iter().enumerate() {
+            if let OperationKind::Serialized { .. } = operation {
+                serialized = Some(self.render_serialized(&run, index, operation).await?);
+            }
+            let jobs = self.prepare(&run, index, operation).await?;
+            if let OperationKind::PerItem { .. } = operation {
+                found += jobs.len();
+            }
+            if !jobs.is_empty() {
+                render = Some((index, operation, jobs));
+            }
+        }
+        if found != 1 {
+            return Err(Error::ExpectedSingleRender { found });
+        }
+
+        if let Some(content) = serialized {
+            writer.write_all(content.as_bytes())?;
+        }
+        let Some((index, operation, jobs)) = render else {
+            return Ok(());
+        };
+        for (section, job) in jobs.into_iter().enumerate() {
+            if let OperationKind::Concatenated { separator, .. } = operation {
+                writer.write_all(separator.prefix(section, job.template_path).as_bytes())?;
+            }
+            let resolved = self.resolve_template(job.template_path);
+            self.scope(&run)
+                .enter(|| {
+                    self.engine()
+                        .render_to_write(&resolved, &job.context, &mut writer)
+                })
+                .map_err(|err| in_operation(err.into(), &self.templates(), &resolved, index))?;
+        }
+        Ok(())
+    }
+}
+
+// Test implementation
+#[cfg(test)]
+// Tests pass `&Path`s where a `Path` would do
+#[allow(clippy::needless_borrows_for_generic_args)]
+mod tests {
+    use super::*;
+    use crate::fs::Fs;
+    use crate::state::{Shared, TemplatePath};
+    use std::time::Duration;
+    use std::collections::HashMap;
+
+    #[derive(Clone, serde::Serialize)]
+    struct User {
+        name: String,
+        age: u32,
+    }
+
+    #[derive(Clone, serde::Serialize)]
+    struct Config {
+        timeout: Duration,
+    }
+
+    #[tokio::test]
+    async fn test_no_params() {
+        async fn get_default_name() -> HashMap<String, String> {
+            let mut map = HashMap::new();
+            map.insert("value".to_string(), "Default".to_string());
+            map
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template_path = tmp_dir.path().join("get_default.jinja");
+        std::fs::write(&template_path, "{{ value }}").unwrap();
+
+        let app = App::from_dir(&tmp_dir.path())
+            .render_operation("get_default.jinja", get_default_name);
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert!(output_dir.join("get_default.jinja").exists());
+        assert_eq!(std::fs::read_to_string(output_dir.join("get_default.jinja")).unwrap(), "Default");
+    }
+
+    #[tokio::test]
+    async fn test_with_templates() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let (base, overlay) = (tmp_dir.path().join("base"), tmp_dir.path().join("overlay"));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::create_dir_all(&overlay).unwrap();
+        std::fs::write(base.join("greet.txt"), "Hello, {{ name }}!").unwrap();
+        std::fs::write(base.join("bye.txt"), "Bye, {{ name }}!").unwrap();
+        std::fs::write(overlay.join("greet.txt"), "Hi, {{ name }}!").unwrap();
+
+        let op = || async { HashMap::from([("name", "Alice")]) };
+        let app = App::new()
+            .with_templates(&base)
+            .with_templates(&overlay)
+            .render_operation("greet.txt", op)
+            .render_operation("bye.txt", op);
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["greet.txt"], "Hi, Alice!");
+        assert_eq!(output["bye.txt"], "Bye, Alice!");
+    }
+
+    #[tokio::test]
+    async fn test_operation_hooks() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (started, completed) = (events.clone(), events.clone());
+        let app = App::new()
+            .with_template_string("greet.txt", "Hello!".to_string())
+            .on_operation_start(move |label| started.lock().unwrap().push(format!("start {label}")))
+            .on_operation_complete(move |label, _, result| {
+                let outcome = if result.is_ok() { "ok" } else { "failed" };
+                completed.lock().unwrap().push(format!("{outcome} {label}"));
+            })
+            .labeled_state_operation("setup", || async {})
+            .render_operation("greet.txt", || async { HashMap::<String, String>::new() })
+            .render_operation("missing.txt", || async { HashMap::<String, String>::new() });
+
+        assert!(app.run_in_memory().await.is_err());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "start setup",
+                "ok setup",
+                "start greet.txt",
+                "ok greet.txt",
+                "start missing.txt",
+                "failed missing.txt",
+            ]
+        );
+    }
+
+    #[cfg(feature = "hashes")]
+    #[tokio::test]
+    async fn test_content_hashes() {
+        let app = App::new()
+            .with_template_string("greet.txt", "{{ name }}".to_string())
+            .render_operation("greet.txt", || async { HashMap::from([("name", "abc")]) });
+
+        app.run_in_memory().await.unwrap();
+        let hashes = app.content_hashes();
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(
+            hashes["greet.txt"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_shared() {
+        struct Schema {
+            tables: Vec<&'static str>,
+        }
+
+        async fn tables(prefix: Data<String>, schema: Shared<Schema>) -> HashMap<String, String> {
+            let prefix = prefix.clone_inner().await;
+            let tables = schema.tables.iter().map(|table| format!("{prefix}{table}"));
+            HashMap::from([("tables".to_string(), tables.collect::<Vec<_>>().join(","))])
+        }
+
+        let app = App::new()
+            .with_template_string("tables.txt", "{{ tables }}".to_string())
+            .with_state("app_".to_string())
+            .with_shared(Schema {
+                tables: vec!["users", "posts"],
+            })
+            .render_operation("tables.txt", tables);
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["tables.txt"], "app_users,app_posts");
+
+        let app = App::new()
+            .with_template_string("tables.txt", "{{ tables }}".to_string())
+            .with_state("app_".to_string())
+            .render_operation("tables.txt", tables);
+        let Error::OperationFailed { source, .. } = app.run_in_memory().await.unwrap_err() else {
+            panic!("expected the operation to fail");
+        };
+        assert!(matches!(*source, Error::ExtractorUnavailable(_)));
+    }
+
+    #[test]
+    fn test_render_str() {
+        let config = EngineConfig::new().with_filter("shout", |value: String| value.to_uppercase());
+        let app = App::new()
+            .with_template_string("name.txt", "{{ name | shout }}".to_string())
+            .with_engine_config(&config)
+            .with_strict_undefined(true);
+
+        let output = app
+            .render_str(
+                "Hello, {% include 'name.txt' %}!",
+                &HashMap::from([("name", "alice")]),
+            )
+            .unwrap();
+        assert_eq!(output, "Hello, ALICE!");
+        assert!(app.render_str("{{ missing }}", &()).is_err());
+        assert_eq!(app.output.get().fs.walk(), ["name.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_emitting_state_operation() {
+        async fn rename(user: Data<String>) -> Vec<(String, Vec<u8>)> {
+            let old = user.replace("Bob".to_string()).await;
+            vec![(
+                "audit/rename.log".to_string(),
+                format!("{old} -> Bob").into_bytes(),
+            )]
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let app = App::new()
+            .with_template_string("greet.txt", "Hello, {{ name }}!".to_string())
+            .with_state("Alice".to_string())
+            .emitting_state_operation(rename)
+            .render_operation("greet.txt", |user: Data<String>| async move {
+                HashMap::from([("name", user.clone_inner().await)])
+            });
+
+        app.run(tmp_dir.path()).await.unwrap();
+        let read = |path: &str| std::fs::read_to_string(tmp_dir.path().join(path)).unwrap();
+        assert_eq!(read("audit/rename.log"), "Alice -> Bob");
+        assert_eq!(read("greet.txt"), "Hello, Bob!");
+    }
+
+    #[tokio::test]
+    async fn test_render_operation_multi() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let entities = move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async { HashMap::from([("name", "User")]) }
+        };
+
+        let app = App::new()
+            .with_template_string("User.ts", "interface {{ name }} {}".to_string())
+            .with_template_string("User.d.ts", "declare interface {{ name }} {}".to_string())
+            .render_operation_multi(&["User.ts", "User.d.ts"], entities);
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["User.ts"], "interface User {}");
+        assert_eq!(output["User.d.ts"], "declare interface User {}");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_with_env_context() {
+        std::env::set_var("QUICKFORM_TEST_ENV_CONTEXT_URL", "postgres://localhost");
+        let app = App::new()
+            .with_template_string(
+                "config.txt",
+                "{{ env.QUICKFORM_TEST_ENV_CONTEXT_URL }} {{ env | length }}".to_string(),
+            )
+            .with_env_context(Some("QUICKFORM_TEST_ENV_CONTEXT_"))
+            .render_operation("config.txt", || async { HashMap::<String, String>::new() });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["config.txt"], "postgres://localhost 1");
+    }
+
+    #[test]
+    fn test_from_dir_filtered() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let root = tmp_dir.path();
+        std::fs::create_dir_all(root.join("models")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join("models/User.ts"), "").unwrap();
+        std::fs::write(root.join("models/User.d.ts"), "").unwrap();
+        std::fs::write(root.join("index.jinja"), "").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+        std::fs::write(root.join(".DS_Store"), "").unwrap();
+        std::fs::write(root.join(".git/config"), "").unwrap();
+
+        let app = App::from_dir_filtered(root, &["ts", "jinja"]);
+        assert_eq!(
+            app.output.get().fs.walk(),
+            ["index.jinja", "models/User.d.ts", "models/User.ts"]
+        );
+        assert!(!app.output.get().fs.exists(".git"));
+    }
+
+    #[tokio::test]
+    async fn test_render_operation_with_retry() {
+        let flaky = |failures: usize| {
+            let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            move || {
+                let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move {
+                    if call < failures {
+                        Err(Error::operation(std::io::Error::other(
+                            "service unavailable",
+                        )))
+                    } else {
+                        Ok(HashMap::from([("status", "ok")]))
+                    }
+                }
+            }
+        };
+        let policy = RetryPolicy::new(3).with_backoff(Duration::from_millis(1));
+        let app = |failures| {
+            App::new()
+                .with_template_string("status.txt", "{{ status }}".to_string())
+                .render_operation_with_retry("status.txt", policy, flaky(failures))
+        };
+
+        let output = app(2).run_in_memory().await.unwrap();
+        assert_eq!(output["status.txt"], "ok");
+
+        let Error::OperationFailed { source, .. } = app(3).run_in_memory().await.unwrap_err()
+        else {
+            panic!("expected the operation to fail");
+        };
+        let Error::RetriesExhausted { attempts, source } = *source else {
+            panic!("expected the retries to be exhausted");
+        };
+        assert_eq!(attempts, 3);
+        assert_eq!(source.to_string(), "service unavailable");
+    }
+
+    #[cfg(feature = "include_dir")]
+    #[tokio::test]
+    async fn test_from_include_dir() {
+        use include_dir::{Dir, DirEntry, File};
+
+        static MODELS: [DirEntry<'static>; 1] = [DirEntry::File(File::new(
+            "models/user.txt",
+            b"User {{ name }}",
+        ))];
+        static TEMPLATES: Dir<'static> = Dir::new(
+            "",
+            &[
+                DirEntry::File(File::new("index.txt", b"Index")),
+                DirEntry::Dir(Dir::new("models", &MODELS)),
+            ],
+        );
+
+        let app = App::from_include_dir(&TEMPLATES)
+            .unwrap()
+            .render_operation("models/user.txt", || async {
+                HashMap::from([("name", "Alice")])
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["models/user.txt"], "User Alice");
+        assert_eq!(app.output.get().fs.walk(), ["index.txt", "models/user.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_trim_trailing_whitespace() {
+        let template = "class {{ name }}:  \n{% if true %}\n\n\n    pass\t\n{% endif %}\n";
+        let app = |trim| {
+            App::new()
+                .with_template_string("model.py", template.to_string())
+                .with_trim_trailing_whitespace(trim)
+                .render_operation("model.py", || async { HashMap::from([("name", "User")]) })
+        };
+
+        let output = app(true).run_in_memory().await.unwrap();
+        assert_eq!(output["model.py"], "class User:\n\n    pass\n");
+        let output = app(false).run_in_memory().await.unwrap();
+        assert_eq!(output["model.py"], "class User:  \n\n\n\n    pass\t\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_output() {
+        let app = App::new()
+            .with_template_string("src/models/user.txt", "User {{ name }}".to_string())
+            .with_template_string("src/index.txt", "Index".to_string())
+            .render_operation("src/models/user.txt", || async {
+                HashMap::from([("name", "Alice")])
+            });
+
+        app.run_in_memory().await.unwrap();
+        assert_eq!(
+            app.read_output("src/models/user.txt").await.unwrap(),
+            b"User Alice"
+        );
+        assert_eq!(
+            app.list_output("src").await.unwrap(),
+            ["index.txt", "models"]
+        );
+        assert!(app.read_output("missing.txt").await.is_err());
+        assert!(app.list_output("src/index.txt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_transformer() {
+        let header = |path: &str, content: String| Ok(format!("// {path}\n{content}"));
+        let shout = |_: &str, content: String| Ok(content.to_uppercase());
+        let app = App::new()
+            .with_template_string("user.ts", "user {{ name }}".to_string())
+            .with_template_string("static.ts", "static".to_string())
+            .with_transformer(header)
+            .with_transformer(shout)
+            .render_operation("user.ts", || async { HashMap::from([("name", "Alice")]) });
+
+        app.run_in_memory().await.unwrap();
+        assert_eq!(
+            app.read_output("user.ts").await.unwrap(),
+            b"// USER.TS\nUSER ALICE"
+        );
+        assert_eq!(app.read_output("static.ts").await.unwrap(), b"static");
+
+        let failing = |path: &str, _: String| -> std::result::Result<String, Error> {
+            Err(Error::operation(std::io::Error::other(format!(
+                "cannot format {path}"
+            ))))
+        };
+        let app = app.with_transformer(failing);
+        let err = app.run_in_memory().await.unwrap_err();
+        assert_eq!(err.to_string(), "cannot format user.ts");
+    }
+
+    #[tokio::test]
+    async fn test_closure_capturing_data() {
+        let config = Data::new("Hello".to_string());
+        let calls = Data::new(0);
+        let captured = (config.clone(), calls.clone());
+        let app = App::new()
+            .with_template_string("greet.txt", "{{ greeting }}".to_string())
+            .with_state("Alice".to_string())
+            .render_operation("greet.txt", move |name: Data<String>| {
+                let (config, calls) = captured.clone();
+                async move {
+                    calls.update(|calls| *calls += 1).await;
+                    let greeting = format!("{}, {}!", *config.read().await, *name.read().await);
+                    HashMap::from([("greeting", greeting)])
+                }
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["greet.txt"], "Hello, Alice!");
+
+        config.set("Hi".to_string()).await;
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["greet.txt"], "Hi, Alice!");
+        assert_eq!(calls.clone_inner().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_graph() {
+        let log = Data::new(Vec::new());
+        let step = |name: &'static str| {
+            move |log: Data<Vec<&'static str>>| async move {
+                log.update(|log| log.push(name)).await;
+            }
+        };
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+
+        let app = App::new()
+            .with_shared_state(log.clone())
+            .state_operation(step("routes"))
+            .node("routes", &["entities", "relationships"])
+            .state_operation(step("relationships"))
+            .node("relationships", &["entities"])
+            .state_operation(step("entities"))
+            .node("entities", &[]);
+        app.run_graph(tmp_dir.path()).await.unwrap();
+        assert_eq!(
+            log.clone_inner().await,
+            ["entities", "relationships", "routes"]
+        );
+
+        let app = app
+            .state_operation(step("audit"))
+            .node("audit", &["cleanup"]);
+        let app = app
+            .state_operation(step("cleanup"))
+            .node("cleanup", &["audit"]);
+        let err = app.run_graph(tmp_dir.path()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Operations audit, cleanup are part of or depend on a dependency cycle"
+        );
+        assert_eq!(log.clone_inner().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_with_cancellation() {
+        let log = Data::new(Vec::new());
+        let app = App::new()
+            .with_template_string("greet.txt", "Hello!".to_string())
+            .with_shared_state(log.clone())
+            .state_operation(|log: Data<Vec<&'static str>>| async move {
+                log.update(|log| log.push("fetched")).await;
+            })
+            .state_operation(|log: Data<Vec<&'static str>>| async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                log.update(|log| log.push("generated")).await;
+            });
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let output_dir = tmp_dir.path().join("output");
+
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+        let result = app.run_with_cancellation(&output_dir, token).await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(log.clone_inner().await, ["fetched"]);
+        assert!(!output_dir.exists());
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = app.run_with_cancellation(&output_dir, token).await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(log.clone_inner().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_render_static() {
+        async fn get_default_name() -> HashMap<String, String> {
+            HashMap::from([("value".to_string(), "Default".to_string())])
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("get_default.jinja"), "{{ value }}").unwrap();
+
+        let app =
+            App::from_dir(tmp_dir.path()).render_static("get_default.jinja", get_default_name);
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["get_default.jinja"], "Default");
+    }
+
+    #[tokio::test]
+    async fn test_from_dir() {
+        async fn double_age(user: Data<User>) -> User {
+            let user = user.clone_inner().await;
+            User {
+                name: user.name,
+                age: user.age * 2,
+            }
+        }
+
+        async fn codify_name(user: Data<User>) -> User {
+            let user = user.clone_inner().await;
+            let new_name = user
+                .name
+                .into_bytes()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<String>>()
+                .join("-");
+            User {
+                name: new_name,
+                age: user.age,
+            }
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+
+        // Create child directory
+        let child_dir = tmp_dir.path().join("child");
+        std::fs::create_dir(&child_dir).unwrap();
+
+        let template_path_double_age = tmp_dir.path().join("double_age.jinja");
+        let template_path_codify_name = child_dir.join("codify_name.jinja");
+
+        std::fs::write(&template_path_double_age, "Age: {{ age }}").unwrap();
+        std::fs::write(&template_path_codify_name, "Name: {{ name }}").unwrap();
+
+        let app = App::from_dir(&tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .render_operation("double_age.jinja", double_age)
+            .render_operation("child/codify_name.jinja", codify_name);
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert!(output_dir.join("double_age.jinja").exists());
+        assert_eq!(std::fs::read_to_string(output_dir.join("double_age.jinja")).unwrap(), "Age: 60");
+        assert!(output_dir.join("child/codify_name.jinja").exists());
+        assert_eq!(std::fs::read_to_string(output_dir.join("child/codify_name.jinja")).unwrap(), "Name: 41-6c-69-63-65");
+    }
+
+    #[tokio::test]
+    async fn test_multiple_params() {
+        async fn get_user_with_timeout(
+            user: Data<User>,
+            config: Data<Config>,
+        ) -> HashMap<String, String> {
+            let mut map = HashMap::new();
+            map.insert("user".to_string(), user.clone_inner().await.name);
+            map.insert("timeout".to_string(), config.clone_inner().await.timeout.as_secs().to_string());
+            map
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template_path = tmp_dir.path().join("multiple_params.jinja");
+        std::fs::write(&template_path, "{{ timeout }} {{ user }}").unwrap();
+
+        let app = App::from_dir(&tmp_dir.path())
+            .with_state(User {
+                name: "Bob".to_string(),
+                age: 25,
+            })
+            .with_state(Config {
+                timeout: Duration::from_secs(30),
+            })
+            .render_operation("multiple_params.jinja", get_user_with_timeout);
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert!(output_dir.join("multiple_params.jinja").exists());
+        assert_eq!(std::fs::read_to_string(output_dir.join("multiple_params.jinja")).unwrap(), "30 Bob");
+    }
+
+    #[tokio::test]
+    async fn test_simple_params() {
+        async fn three_params(x: Data<i32>, y: Data<i32>, z: Data<i32>) -> HashMap<String, i32> {
+            let x = x.clone_inner().await;
+            let y = y.clone_inner().await;
+            let z = z.clone_inner().await;
+            let mut map = HashMap::new();
+            map.insert("sum".to_string(), x + y + z);
+            map
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template_path = tmp_dir.path().join("simple_params.jinja");
+        std::fs::write(&template_path, "{{ sum }}").unwrap();
+
+        let app = App::from_dir(&tmp_dir.path())
+            .with_state(1)
+            .with_state(2)
+            .with_state(3)
+            .render_operation("simple_params.jinja", three_params);
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert!(output_dir.join("simple_params.jinja").exists());
+        assert_eq!(std::fs::read_to_string(output_dir.join("simple_params.jinja")).unwrap(), "6");
+    }
+
+    #[tokio::test]
+    async fn test_state_operation_single_state() {
+        let app = App::default()
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+            });
+
+        // Run the app
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        app.run(tmp_dir.path()).await.unwrap();
+
+        // Verify the state was updated
+        assert_eq!(
+            app.state.clone_inner().await.name,
+            "Bob"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_operation_multiple_states() {
+        let app = App::default()
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .with_state(Config {
+                timeout: Duration::from_secs(30),
+            })
+            .state_operation(|user: Data<User>, config: Data<Config>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+                config.update(|c| c.timeout = Duration::from_secs(60)).await;
+            });
+
+        // Run the app
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        app.run(tmp_dir.path()).await.unwrap();
+
+        // Verify both states were updated
+        assert_eq!(
+            app.state.0.clone_inner().await.name,
+            "Bob"
+        );
+        assert_eq!(
+            app.state.1.clone_inner().await.timeout,
+            Duration::from_secs(60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_operation_chain() {
+        let app = App::default()
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+            })
+            .state_operation(|user: Data<User>| async move {
+                let current = user.clone_inner().await;
+                user.update(|u| u.name = format!("{}-modified", current.name)).await;
+            });
+
+        // Run the app
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        app.run(tmp_dir.path()).await.unwrap();
+
+        // Verify the state was updated by both operations
+        assert_eq!(
+            app.state.clone_inner().await.name,
+            "Bob-modified"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mixed_operations() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template_path = tmp_dir.path().join("user.jinja");
+        std::fs::write(&template_path, "Name: {{ name }}").unwrap();
+
+        let app = App::from_dir(&tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+
+        // Verify the state was updated
+        assert_eq!(
+            app.state.clone_inner().await.name,
+            "Bob"
+        );
+
+        // Verify the template was rendered with the updated state
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("user.jinja")).unwrap(),
+            "Name: Bob"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shared_render_context() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("shared.jinja"),
+            "{{ project.name }} {{ project.version }} {{ name }}",
+        )
+        .unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_shared_render_context(serde_json::json!({
+                "project": { "name": "shop", "version": "1.0" },
+                "name": "shared",
+            }))
+            .render_operation("shared.jinja", || async {
+                serde_json::json!({ "project": { "version": "2.0" }, "name": "Alice" })
+            });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("shared.jinja")).unwrap(),
+            "shop 2.0 Alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_size_limit() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("loop.jinja"),
+            "{% for i in range(1000) %}{{ i }}{% endfor %}",
+        )
+        .unwrap();
+        std::fs::write(tmp_dir.path().join("small.jinja"), "ok").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_output_size_limit(100)
+            .render_operation("small.jinja", || async { HashMap::<String, String>::new() })
+            .render_operation("loop.jinja", || async { HashMap::<String, String>::new() });
+
+        let err = app.run(tmp_dir.path().join("output")).await.unwrap_err();
+        match err {
+            Error::OutputTooLarge { template, limit } => {
+                assert_eq!(template, "loop.jinja");
+                assert_eq!(limit, 100);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_template_source_editing() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("greet.jinja"), "Hello {{ name }}").unwrap();
+
+        let mut app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .render_operation("greet.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+        assert_eq!(
+            app.get_template_source("greet.jinja").unwrap(),
+            "Hello {{ name }}"
+        );
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+            "Hello Alice"
+        );
+
+        // Rendering does not clobber the source, and edits are picked up
+        assert_eq!(
+            app.get_template_source("greet.jinja").unwrap(),
+            "Hello {{ name }}"
+        );
+        app.set_template_source("greet.jinja", "Bye {{ name }}")
+            .unwrap();
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+            "Bye Alice"
+        );
+
+        assert!(app.get_template_source("missing.jinja").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_template_resolver() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("model.ts.jinja"), "ts {{ value }}").unwrap();
+        std::fs::write(tmp_dir.path().join("default.jinja"), "default {{ value }}").unwrap();
+
+        let known = ["model.ts.jinja"];
+        let app = App::from_dir(tmp_dir.path())
+            .with_template_resolver(move |name| {
+                let candidate = format!("{name}.ts.jinja");
+                if known.contains(&candidate.as_str()) {
+                    Some(candidate)
+                } else if name.ends_with(".jinja") {
+                    None
+                } else {
+                    Some("default.jinja".to_string())
+                }
+            })
+            .render_operation("model", || async {
+                HashMap::from([("value".to_string(), "a".to_string())])
+            })
+            .render_operation("route", || async {
+                HashMap::from([("value".to_string(), "b".to_string())])
+            })
+            .render_operation("default.jinja", || async {
+                HashMap::from([("value".to_string(), "c".to_string())])
+            });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        let read = |name: &str| std::fs::read_to_string(output_dir.join(name)).unwrap();
+        assert_eq!(read("model"), "ts a");
+        assert_eq!(read("route"), "default b");
+        assert_eq!(read("default.jinja"), "default c");
+    }
+
+    #[tokio::test]
+    async fn test_render_operation_with_meta() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("model.ts.jinja"), "class {{ name }} {}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "User".to_string(),
+                age: 30,
+            })
+            .render_operation_with_meta(
+                "model.ts.jinja",
+                "src/models/User.ts",
+                serde_json::json!({ "generated-from": "model.ts.jinja", "entity": "User" }),
+                |user: Data<User>| async move { user.clone_inner().await },
+            );
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("src/models/User.ts")).unwrap(),
+            "class User {}"
+        );
+
+        let manifest: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(output_dir.join(META_MANIFEST_PATH)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest,
+            serde_json::json!({
+                "version": 1,
+                "files": {
+                    "src/models/User.ts": { "generated-from": "model.ts.jinja", "entity": "User" }
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_to_writer() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "Name: {{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+            })
+            .emitting_state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name.push_str(" Jr.")).await;
+                vec![("log.txt".to_string(), b"renamed".to_vec())]
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let mut out = Vec::new();
+        app.render_to_writer(&mut out).await.unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Name: Bob Jr.");
+        assert!(app.read_output("log.txt").await.is_err());
+
+        let app = app.render_operation("user.jinja", |user: Data<User>| async move {
+            user.clone_inner().await
+        });
+        assert!(matches!(
+            app.render_to_writer(Vec::new()).await,
+            Err(Error::ExpectedSingleRender { found: 2 })
+        ));
+
+        // Items are resolved once, both to count and to render them
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = App::from_dir(tmp_dir.path())
+            .with_collection_state(vec![User {
+                name: "Carol".to_string(),
+                age: 40,
+            }])
+            .render_per_item("user.jinja", move |user: &User| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                user.name.clone()
+            });
+        let mut out = Vec::new();
+        app.render_to_writer(&mut out).await.unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Name: Carol");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_runner_steps() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "Name: {{ name }}").unwrap();
+
+        let mut runner = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            })
+            .into_runner();
+
+        assert_eq!(runner.remaining(), 2);
+        assert!(matches!(runner.step().await, Some(Ok(StepResult::State))));
+        assert_eq!(runner.state().clone_inner().await.name, "Bob");
+
+        match runner.step().await {
+            Some(Ok(StepResult::Rendered { path, content })) => {
+                assert_eq!(path, "user.jinja");
+                assert_eq!(content, "Name: Bob");
+            }
+            _ => panic!("expected a rendered step"),
+        }
+        assert!(runner.step().await.is_none());
+
+        let output_dir = tmp_dir.path().join("output");
+        runner.finish(&output_dir).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("user.jinja")).unwrap(),
+            "Name: Bob"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_wire() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("entities.jinja"),
+            "{# quickform: context=entities #}{% for e in items %}{{ e }};{% endfor %}",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("orphan.jinja"),
+            "{# quickform: context=missing #}untouched",
+        )
+        .unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .named_operation("entities", || async {
+                serde_json::json!({ "items": ["User", "Order"] })
+            })
+            .auto_wire();
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("entities.jinja")).unwrap(),
+            "User;Order;"
+        );
+        // Templates wired to unknown operations are left as they are
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("orphan.jinja")).unwrap(),
+            "{# quickform: context=missing #}untouched"
+        );
+    }
+
+    #[cfg(feature = "markdown")]
+    #[tokio::test]
+    async fn test_markdown_transform() {
+        use pulldown_cmark::{Event, Tag};
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("doc.md"), "# {{ name }}\n\nHello").unwrap();
+        std::fs::write(tmp_dir.path().join("plain.txt"), "# {{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .with_markdown_transform(|_, events| {
+                // Uppercase every piece of heading text
+                let mut in_heading = false;
+                events
+                    .into_iter()
+                    .map(|event| match event {
+                        Event::Start(Tag::Heading { .. }) => {
+                            in_heading = true;
+                            event
+                        }
+                        Event::End(_) => {
+                            in_heading = false;
+                            event
+                        }
+                        Event::Text(text) if in_heading => Event::Text(text.to_uppercase().into()),
+                        other => other,
+                    })
+                    .collect()
+            })
+            .render_operation("doc.md", |user: Data<User>| async move {
+                user.clone_inner().await
+            })
+            .render_operation("plain.txt", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("doc.md")).unwrap(),
+            "# ALICE\n\nHello"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("plain.txt")).unwrap(),
+            "# Alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clone_shares_state() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "Name: {{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+        let handler_app = app.clone();
+
+        app.state.update(|u| u.name = "Bob".to_string()).await;
+
+        let output_dir = tmp_dir.path().join("output");
+        handler_app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("user.jinja")).unwrap(),
+            "Name: Bob"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clones_keep_their_own_output() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("greet.txt"), "Hello {{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation("greet.txt", || async { HashMap::from([("name", "Alice")]) });
+        let mut formal = app.clone();
+        formal
+            .set_template_source("greet.txt", "Good day, {{ name }}")
+            .unwrap();
+
+        let plain_dir = tmp_dir.path().join("plain");
+        let formal_dir = tmp_dir.path().join("formal");
+        let (plain, formal_result) = tokio::join!(app.run(&plain_dir), formal.run(&formal_dir));
+        plain.unwrap();
+        formal_result.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(plain_dir.join("greet.txt")).unwrap(),
+            "Hello Alice"
+        );
+        assert_eq!(
+            std::fs::read_to_string(formal_dir.join("greet.txt")).unwrap(),
+            "Good day, Alice"
+        );
+        assert_eq!(
+            app.get_template_source("greet.txt").unwrap(),
+            "Hello {{ name }}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_bundle() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("model.jinja"), "model {{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("dto.jinja"), "dto {{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("broken.jinja"), "{{ name | nope }}").unwrap();
+
+        let entity = || async { HashMap::from([("name".to_string(), "User".to_string())]) };
+        let app = App::from_dir(tmp_dir.path()).render_bundle(
+            entity,
+            &[("model.jinja", "User.ts"), ("dto.jinja", "User.dto.ts")],
+        );
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("User.ts")).unwrap(),
+            "model User"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("User.dto.ts")).unwrap(),
+            "dto User"
+        );
+
+        // A failing template prevents every file of the bundle from being written
+        let app = App::from_dir(tmp_dir.path()).render_bundle(
+            entity,
+            &[
+                ("model.jinja", "Order.ts"),
+                ("broken.jinja", "Order.dto.ts"),
+            ],
+        );
+        let mut runner = app.into_runner();
+        assert!(matches!(runner.step().await, Some(Err(_))));
+        let app = runner.into_app();
+        assert!(app.output.get().fs.read_file("Order.ts").is_err());
+    }
+
+    #[test]
+    fn test_state_type_names() {
+        assert!(App::default().state_type_names().is_empty());
+
+        let app = App::default()
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .with_state(Config {
+                timeout: Duration::from_secs(10),
+            });
+        assert_eq!(
+            app.state_type_names(),
+            vec![
+                std::any::type_name::<User>(),
+                std::any::type_name::<Config>()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collision_policy() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("entity.jinja"), "{{ name }}").unwrap();
+
+        let user = || async { HashMap::from([("name".to_string(), "User".to_string())]) };
+        let users = || async { HashMap::from([("name".to_string(), "user".to_string())]) };
+        let outputs = [("entity.jinja", "user.ts")];
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_collision_policy(CollisionPolicy::Error)
+            .render_bundle(user, &outputs)
+            .render_bundle(users, &outputs);
+        let result = app.run(tmp_dir.path().join("error")).await;
+        assert!(matches!(result, Err(Error::OutputCollision { path }) if path == "user.ts"));
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_collision_policy(CollisionPolicy::Rename)
+            .render_bundle(user, &outputs)
+            .render_bundle(users, &outputs);
+        let output_dir = tmp_dir.path().join("rename");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("user.ts")).unwrap(),
+            "User"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("user-1.ts")).unwrap(),
+            "user"
+        );
+
+        // Running again starts from a clean set of written paths
+        app.run(&output_dir).await.unwrap();
+        assert!(!output_dir.join("user-2.ts").exists());
+
+        // Concurrent runs of clones don't claim each other's paths
+        let app = App::from_dir(tmp_dir.path())
+            .with_collision_policy(CollisionPolicy::Error)
+            .render_bundle(user, &outputs);
+        let clone = app.clone();
+        let (first, second) = tokio::join!(
+            app.run(tmp_dir.path().join("first")),
+            clone.run(tmp_dir.path().join("second"))
+        );
+        first.unwrap();
+        second.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_engine_config() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("greet.jinja"),
+            "{{ name | shout }} from {{ generator }}",
+        )
+        .unwrap();
+
+        let config = EngineConfig::new()
+            .with_filter("shout", |value: String| value.to_uppercase())
+            .with_global("generator", "quickform")
+            .with_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+
+        for name in ["alice", "bob"] {
+            let app = App::from_dir(tmp_dir.path())
+                .with_engine_config(&config)
+                .render_operation("greet.jinja", move || async move {
+                    HashMap::from([("name".to_string(), name.to_string())])
+                });
+            let output_dir = tmp_dir.path().join(name);
+            app.run(&output_dir).await.unwrap();
+            assert_eq!(
+                std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+                format!("{} from quickform", name.to_uppercase())
+            );
+        }
+
+        // Strict undefined behavior rejects missing variables
+        let app = App::from_dir(tmp_dir.path())
+            .with_engine_config(&config)
+            .render_operation("greet.jinja", || async { HashMap::<String, String>::new() });
+        assert!(app.run(tmp_dir.path().join("strict")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_strict_undefined() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("greet.jinja"), "Hello, {{ naem }}!").unwrap();
+        let op = || async { HashMap::from([("name".to_string(), "Alice".to_string())]) };
+
+        let app = App::from_dir(tmp_dir.path()).render_operation("greet.jinja", op);
+        assert_eq!(
+            app.run_in_memory().await.unwrap()["greet.jinja"],
+            "Hello, !"
+        );
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_strict_undefined(true)
+            .render_operation("greet.jinja", op);
+        let err = app.run_in_memory().await.unwrap_err();
+        assert!(matches!(err, Error::Render { .. }), "{err:?}");
+    }
+
+    #[cfg(feature = "custom_syntax")]
+    #[tokio::test]
+    async fn test_with_syntax() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("greet.jinja"),
+            "<% if name %>Hello, <= name =>! {{ literal }}<% endif %>",
+        )
+        .unwrap();
+
+        let syntax = minijinja::syntax::SyntaxConfig::builder()
+            .block_delimiters("<%", "%>")
+            .variable_delimiters("<=", "=>")
+            .build()
+            .unwrap();
+        let app = App::from_dir(tmp_dir.path())
+            .with_syntax(syntax)
+            .render_operation("greet.jinja", || async {
+                HashMap::from([("name".to_string(), "Alice".to_string())])
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["greet.jinja"], "Hello, Alice! {{ literal }}");
+    }
+
+    #[cfg(feature = "tera")]
+    #[tokio::test]
+    async fn test_tera_engine() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("base.tera"),
+            "[{% block body %}{% endblock body %}]",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("model.tera"),
+            "{% extends \"base.tera\" %}\
+             {% block body %}{{ fields | json_encode() }}{% endblock body %}",
+        )
+        .unwrap();
+        // Files that aren't Tera templates don't break other renders
+        std::fs::write(tmp_dir.path().join("notes.txt"), "Open with {{ and").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_engine(Engine::Tera)
+            .render_operation("model.tera", || async {
+                HashMap::from([("fields", vec!["id", "name"])])
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["model.tera"], r#"[["id","name"]]"#);
+        assert_eq!(
+            app.render_str("{{ 1 + 2 }}", &HashMap::<String, String>::new())
+                .unwrap(),
+            "3"
+        );
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_engine(Engine::Tera)
+            .render_operation("notes.txt", || async { HashMap::from([("a", 1)]) });
+        let Err(Error::Render { source, .. }) = app.run_in_memory().await else {
+            panic!("expected the asset to fail to parse");
+        };
+        assert!(source.to_string().contains("Failed to parse 'notes.txt'"));
+
+        // Tera contexts must be maps
+        let app = App::from_dir(tmp_dir.path())
+            .with_engine(Engine::Tera)
+            .render_operation("model.tera", || async { vec!["id", "name"] });
+        assert!(app.run_in_memory().await.is_err());
+
+        // Configuration Tera can't apply fails renders instead of being ignored
+        let app = App::from_dir(tmp_dir.path())
+            .with_engine(Engine::Tera)
+            .with_strict_undefined(true)
+            .render_operation("model.tera", || async {
+                HashMap::from([("fields", vec!["id"])])
+            });
+        let Err(Error::Render { source, .. }) = app.run_in_memory().await else {
+            panic!("expected the configuration to be rejected");
+        };
+        assert!(source.to_string().contains("doesn't support"));
+    }
+
+    #[cfg(feature = "tera")]
+    #[tokio::test]
+    async fn test_tera_builtin_functions() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("greet.tera"),
+            "{% set user = ref(name=\"user\") %}{{ t(key=\"greeting\") }}, {{ user.name }}!",
+        )
+        .unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_engine(Engine::Tera)
+            .with_translations(
+                "en",
+                HashMap::from([("greeting".to_string(), "Hello".to_string())]),
+            )
+            .with_translations(
+                "es",
+                HashMap::from([("greeting".to_string(), "Hola".to_string())]),
+            )
+            .with_locale("es")
+            .named_operation("user", || async { HashMap::from([("name", "Alice")]) })
+            .render_operation("greet.tera", || async { HashMap::from([("a", 1)]) });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["greet.tera"], "Hola, Alice!");
+    }
+
+    #[tokio::test]
+    async fn test_translations() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("greet.jinja"),
+            "{{ t(\"greeting\") }}, {{ name }}! {{ t(\"farewell\") }}",
+        )
+        .unwrap();
+
+        let op = || async { HashMap::from([("name".to_string(), "Alice".to_string())]) };
+        let app = App::from_dir(tmp_dir.path())
+            .with_translations(
+                "en",
+                HashMap::from([("greeting".to_string(), "Hello".to_string())]),
+            )
+            .with_translations(
+                "es",
+                HashMap::from([("greeting".to_string(), "Hola".to_string())]),
+            )
+            .render_operation("greet.jinja", op);
+
+        let output_dir = tmp_dir.path().join("en");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+            "Hello, Alice! farewell"
+        );
+
+        let app = app.with_locale("es");
+        let output_dir = tmp_dir.path().join("es");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+            "Hola, Alice! farewell"
+        );
+
+        // Clones keep the locale they were created with
+        let french = app.clone().with_locale("fr");
+        let output_dir = tmp_dir.path().join("es-again");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+            "Hola, Alice! farewell"
+        );
+        let output_dir = tmp_dir.path().join("fr");
+        french.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("greet.jinja")).unwrap(),
+            "greeting, Alice! farewell"
+        );
+        let french = french.with_strict_translations();
+        assert!(french.run(tmp_dir.path().join("fr-strict")).await.is_err());
+
+        let app = app.with_strict_translations();
+        assert!(app.run(tmp_dir.path().join("strict")).await.is_err());
+    }
+
+    #[test]
+    fn test_planned_output_paths() {
+        let op = || async { HashMap::<String, String>::new() };
+        let app = App::default()
+            .render_operation("a.jinja", op)
+            .state_operation(|| async {})
+            .render_bundle(op, &[("b.jinja", "b.ts"), ("c.jinja", "c.ts")])
+            .render_operation_with_meta("d.jinja", "d.ts", serde_json::Value::Null, op);
+
+        assert_eq!(
+            app.planned_output_paths(),
+            vec!["a.jinja", "b.ts", "c.ts", "d.ts"]
+        );
+        assert_eq!(app.render_targets(), app.planned_output_paths());
+        assert_eq!(app.operation_count(), 4);
+    }
+
+    #[test]
+    fn test_planned_output_paths_dynamic() {
+        let op = || async { HashMap::<String, String>::new() };
+        let app = App::default()
+            .render_operation("a.jinja", op)
+            .render_per_item("entity.jinja", |name: &String| format!("{name}.ts"))
+            .emitting_state_operation(|| async { Vec::<(String, Vec<u8>)>::new() });
+
+        assert_eq!(
+            app.planned_output_paths(),
+            vec![
+                "a.jinja",
+                "<dynamic: entity.jinja>",
+                "<dynamic: emitting state operation 2>"
+            ]
+        );
+        assert_eq!(app.render_targets(), vec!["a.jinja"]);
+    }
+
+    #[tokio::test]
+    async fn test_transform_templates() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("greet.jinja"), "Hello {{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("unused.jinja"), "unused").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .transform_templates(|fs| {
+                for path in fs.walk() {
+                    let source = fs.remove_file(&path).unwrap();
+                    if path == "unused.jinja" {
+                        continue;
+                    }
+                    let mut patched = b"// generated\n".to_vec();
+                    patched.extend(source);
+                    fs.write_file(&format!("src/{path}"), patched).unwrap();
+                }
+            })
+            .render_operation("src/greet.jinja", || async {
+                HashMap::from([("name".to_string(), "Alice".to_string())])
+            });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("src/greet.jinja")).unwrap(),
+            "// generated\nHello Alice"
+        );
+        assert!(!output_dir.join("unused.jinja").exists());
+    }
+
+    #[tokio::test]
+    async fn test_ref_named_output() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("index.jinja"),
+            "{% for entity in ref(\"entities\") %}{{ entity }} {% endfor %}",
+        )
+        .unwrap();
+
+        let entities = || async { vec!["User", "Order"] };
+        let empty = || async { HashMap::<String, String>::new() };
+
+        // Referencing an operation that has not run yet fails the render
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation("index.jinja", empty)
+            .named_operation("entities", entities);
+        let err = app.run(tmp_dir.path().join("early")).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Render { operation: 0, source, .. } if source.to_string().contains("entities")
+        ));
+
+        let app = App::from_dir(tmp_dir.path())
+            .named_operation("entities", entities)
+            .render_operation("index.jinja", empty);
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("index.jinja")).unwrap(),
+            "User Order "
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_per_item() {
+        #[derive(Serialize)]
+        struct Entity {
+            name: String,
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("entity.jinja"), "model {{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_collection_state(vec![
+                Entity {
+                    name: "User".to_string(),
+                },
+                Entity {
+                    name: "Order".to_string(),
+                },
+            ])
+            .render_per_item("entity.jinja", |entity: &Entity| {
+                format!("models/{}.ts", entity.name)
+            });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("models/User.ts")).unwrap(),
+            "model User"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("models/Order.ts")).unwrap(),
+            "model Order"
+        );
+
+        let app = App::from_dir(tmp_dir.path())
+            .render_per_item("entity.jinja", |entity: &Entity| entity.name.clone());
+        let result = app.run(tmp_dir.path().join("missing")).await;
+        assert!(matches!(
+            result,
+            Err(Error::OperationFailed { source, .. })
+                if matches!(*source, Error::MissingCollection(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_assert_matches_golden() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template_dir = tmp_dir.path().join("templates");
+        std::fs::create_dir(&template_dir).unwrap();
+        std::fs::write(template_dir.join("greet.jinja"), "Hello {{ name }}").unwrap();
+
+        let app = App::from_dir(&template_dir).render_operation_with_meta(
+            "greet.jinja",
+            "greet.txt",
+            serde_json::Value::Null,
+            || async { HashMap::from([("name".to_string(), "Alice".to_string())]) },
+        );
+
+        let golden_dir = tmp_dir.path().join("golden");
+        std::fs::create_dir(&golden_dir).unwrap();
+        std::fs::write(golden_dir.join("greet.jinja"), "Hello {{ name }}").unwrap();
+        std::fs::write(golden_dir.join("greet.txt"), "Hello Bob").unwrap();
+
+        let Err(Error::GoldenMismatch(diffs)) = app.assert_matches_golden(&golden_dir).await else {
+            panic!("expected the output to differ from the golden directory");
+        };
+        assert_eq!(
+            diffs,
+            vec![DiffEntry::Changed {
+                path: "greet.txt".to_string(),
+                expected: "Hello Bob".to_string(),
+                actual: "Hello Alice".to_string(),
+            }]
+        );
+
+        std::fs::write(golden_dir.join("greet.txt"), "Hello Alice").unwrap();
+        app.assert_matches_golden(&golden_dir).await.unwrap();
+
+        let missing = tmp_dir.path().join("missing");
+        assert!(matches!(
+            app.assert_matches_golden(&missing).await,
+            Err(Error::FileSystemError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_render_first_available() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::create_dir(tmp_dir.path().join("defaults")).unwrap();
+        std::fs::write(tmp_dir.path().join("defaults/model.jinja"), "default").unwrap();
+
+        let op = || async { HashMap::<String, String>::new() };
+        let candidates = ["overrides/model.jinja", "defaults/model.jinja"];
+        let app = App::from_dir(tmp_dir.path()).render_first_available(&candidates, "model.ts", op);
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("model.ts")).unwrap(),
+            "default"
+        );
+
+        let app = app.render_first_available(&["a.jinja", "b.jinja"], "other.ts", op);
+        let result = app.run(&output_dir).await;
+        assert!(matches!(
+            result,
+            Err(Error::NoTemplateAvailable { tried }) if tried == ["a.jinja", "b.jinja"]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_render_concatenated() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let users = "CREATE TABLE {{ prefix }}users";
+        let orders = "CREATE TABLE {{ prefix }}orders";
+        std::fs::write(tmp_dir.path().join("users.jinja"), users).unwrap();
+        std::fs::write(tmp_dir.path().join("orders.jinja"), orders).unwrap();
+
+        let op = || async { HashMap::from([("prefix".to_string(), "app_".to_string())]) };
+        let sections = ["users.jinja", "orders.jinja"];
+        let app = App::from_dir(tmp_dir.path())
+            .render_concatenated("plain.sql", &sections, op, ";\n")
+            .render_concatenated(
+                "headed.sql",
+                &sections,
+                op,
+                Separator::new(";\n").with_header("-- {template}\n"),
+            );
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("plain.sql")).unwrap(),
+            "CREATE TABLE app_users;\nCREATE TABLE app_orders"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("headed.sql")).unwrap(),
+            "-- users.jinja\nCREATE TABLE app_users;\n-- orders.jinja\nCREATE TABLE app_orders"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_six_states() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("sum.jinja"), "{{ sum }}").unwrap();
+
+        async fn sum(
+            a: Data<u8>,
+            b: Data<u16>,
+            c: Data<u32>,
+            d: Data<u64>,
+            e: Data<i32>,
+            f: Data<i64>,
+        ) -> HashMap<String, i64> {
+            let sum = a.clone_inner().await as i64
+                + b.clone_inner().await as i64
+                + c.clone_inner().await as i64
+                + d.clone_inner().await as i64
+                + e.clone_inner().await as i64
+                + f.clone_inner().await;
+            HashMap::from([("sum".to_string(), sum)])
+        }
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(1u8)
+            .with_state(2u16)
+            .with_state(3u32)
+            .with_state(4u64)
+            .with_state(5i32)
+            .with_state(6i64)
+            .render_operation("sum.jinja", sum);
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("sum.jinja")).unwrap(),
+            "21"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_in_memory() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "{{ name }} ({{ age }})").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|user| user.age += 1).await;
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(
+            output,
+            HashMap::from([("user.jinja".to_string(), "Alice (31)".to_string())])
+        );
+        assert!(std::fs::read_dir(tmp_dir.path()).unwrap().count() == 1);
+    }
+
+    #[tokio::test]
+    async fn test_render_error_context() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("ok.jinja"), "ok").unwrap();
+        std::fs::write(tmp_dir.path().join("broken.jinja"), "{% if %}").unwrap();
+
+        let op = || async { HashMap::<String, String>::new() };
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation("ok.jinja", op)
+            .render_operation("missing.jinja", op);
+        let err = app.run(tmp_dir.path().join("missing")).await.unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::Render { template, operation: 1, .. } if template == "missing.jinja"
+        ));
+        assert!(err.to_string().contains("missing.jinja"));
+
+        let app = App::from_dir(tmp_dir.path())
+            .state_operation(|| async {})
+            .render_operation("broken.jinja", op);
+        let err = app.run(tmp_dir.path().join("broken")).await.unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::Render { template, operation: 1, .. } if template == "broken.jinja"
+        ));
+        assert!(err
+            .to_string()
+            .ends_with("--> broken.jinja:1\n> 1 | {% if %}"));
+    }
+
+    #[tokio::test]
+    async fn test_render_error_snippet() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::create_dir(tmp_dir.path().join("models")).unwrap();
+        std::fs::write(
+            tmp_dir.path().join("models/model.jinja"),
+            "struct {{ name }} {\n{% include 'fields.jinja' %}\n}",
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("models/fields.jinja"),
+            "  id: u64,\n{% for field in fields %}\n  {{ field.name }}: {{ field.ty | nope }},\n\
+             {% endfor %}\n",
+        )
+        .unwrap();
+
+        let app = App::from_dir(tmp_dir.path()).render_operation("models/model.jinja", || async {
+            serde_json::json!({"name": "User", "fields": [{"name": "email", "ty": "String"}]})
+        });
+
+        let err = app.run_in_memory().await.unwrap_err();
+        let Error::Render { template, line, .. } = &err else {
+            panic!("expected a render error, got {err:?}");
+        };
+        assert_eq!(template, "models/model.jinja");
+        assert_eq!(*line, Some(3));
+        assert_eq!(
+            err.to_string(),
+            "Failed to render template models/model.jinja in operation 0\n\
+             \x20 --> models/fields.jinja:3\n\
+             \x20 2 | {% for field in fields %}\n\
+             > 3 |   {{ field.name }}: {{ field.ty | nope }},\n\
+             \x20 4 | {% endfor %}"
+        );
+    }
+
+    #[test]
+    fn test_try_from_dir() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let missing = tmp_dir.path().join("missing");
+
+        assert!(matches!(
+            App::try_from_dir(&missing),
+            Err(Error::FileSystemError(_))
+        ));
+        assert!(App::try_from_dir(tmp_dir.path()).is_ok());
+        assert!(std::panic::catch_unwind(|| App::from_dir(&missing)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_render_operation_to() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::create_dir(tmp_dir.path().join("templates")).unwrap();
+        std::fs::write(
+            tmp_dir.path().join("templates/model.ts.jinja"),
+            "export interface {{ name }} {}",
+        )
+        .unwrap();
+
+        let app = App::from_dir(tmp_dir.path()).render_operation_to(
+            "templates/model.ts.jinja",
+            "src/models/User.ts",
+            || async { HashMap::from([("name".to_string(), "User".to_string())]) },
+        );
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(
+            output,
+            HashMap::from([(
+                "src/models/User.ts".to_string(),
+                "export interface User {}".to_string()
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capturing_operation() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template = "{{ greeting }}, {{ name }}";
+        std::fs::write(tmp_dir.path().join("greet.jinja"), template).unwrap();
+
+        let greeting = String::from("Hello");
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let state_log = log.clone();
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(move |user: Data<User>| {
+                let log = state_log.clone();
+                async move {
+                    let name = user.clone_inner().await.name;
+                    log.lock().unwrap().push(name);
+                }
+            })
+            .render_operation("greet.jinja", move |user: Data<User>| {
+                let greeting = greeting.clone();
+                async move {
+                    HashMap::from([
+                        ("greeting".to_string(), greeting),
+                        ("name".to_string(), user.clone_inner().await.name),
+                    ])
+                }
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["greet.jinja"], "Hello, Alice");
+        assert_eq!(*log.lock().unwrap(), vec!["Alice"]);
+    }
+
+    #[tokio::test]
+    async fn test_with_template_string() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let app = App::from_dir(tmp_dir.path())
+            .with_template_string("inline.jinja", "Hello, {{ name }}!".to_string())
+            .render_operation("inline.jinja", || async {
+                HashMap::from([("name".to_string(), "Alice".to_string())])
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["inline.jinja"], "Hello, Alice!");
+    }
+
+    #[tokio::test]
+    async fn test_run_parallel() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let out_dir = tempdir::TempDir::new("out").unwrap();
+        std::fs::write(tmp_dir.path().join("a.jinja"), "a {{ age }}").unwrap();
+        std::fs::write(tmp_dir.path().join("b.jinja"), "b {{ age }}").unwrap();
+
+        // Each render waits for the other, so they only finish if run concurrently
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let render = |barrier: Arc<tokio::sync::Barrier>| {
+            move |user: Data<User>| {
+                let barrier = barrier.clone();
+                async move {
+                    barrier.wait().await;
+                    HashMap::from([("age".to_string(), user.clone_inner().await.age)])
+                }
+            }
+        };
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.age += 1).await;
+            })
+            .render_operation("a.jinja", render(barrier.clone()))
+            .render_operation("b.jinja", render(barrier));
+
+        tokio::time::timeout(Duration::from_secs(5), app.run_parallel(out_dir.path()))
+            .await
+            .expect("render operations did not run concurrently")
+            .unwrap();
+
+        let a = std::fs::read_to_string(out_dir.path().join("a.jinja")).unwrap();
+        let b = std::fs::read_to_string(out_dir.path().join("b.jinja")).unwrap();
+        assert_eq!((a.as_str(), b.as_str()), ("a 31", "b 31"));
+    }
+
+    #[tokio::test]
+    async fn test_fallible_operations() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "{{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .try_state_operation(|user: Data<User>| async move {
+                user.update(|u| u.age += 1).await;
+                Ok::<_, Error>(())
+            })
+            .try_render_operation("user.jinja", |user: Data<User>| async move {
+                Ok::<_, std::io::Error>(user.clone_inner().await)
+            });
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["user.jinja"], "Alice");
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            })
+            .try_render_operation("user.jinja", |_: Data<User>| async move {
+                Err::<User, _>(std::io::Error::other("request timed out"))
+            })
+            .state_operation(|user: Data<User>| async move {
+                user.update(|u| u.name = "Bob".to_string()).await;
+            });
+
+        let err = app.run(tmp_dir.path().join("output")).await.unwrap_err();
+        match err {
+            Error::OperationFailed {
+                operation,
+                label,
+                source,
+            } => {
+                assert_eq!(operation, 1);
+                assert_eq!(label, "user.jinja");
+                assert!(matches!(*source, Error::IOError(_)));
+                assert_eq!(source.to_string(), "IO error");
+            }
+            other => panic!("expected an operation failure, got {other:?}"),
+        }
+        assert_eq!(app.state.clone_inner().await.name, "Alice");
+        assert!(!tmp_dir.path().join("output").exists());
+    }
+
+    #[tokio::test]
+    async fn test_json_operation() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .json_operation("entities/user.json", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(
+            output["entities/user.json"],
+            "{\n  \"name\": \"Alice\",\n  \"age\": 30\n}"
+        );
+        assert_eq!(app.planned_output_paths(), vec!["entities/user.json"]);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[tokio::test]
+    async fn test_yaml_operation() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Manifest {
+            name: String,
+            age: u32,
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .yaml_operation("ci/user.yaml", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        let manifest: Manifest = serde_yaml::from_str(&output["ci/user.yaml"]).unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                name: "Alice".to_string(),
+                age: 30
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_global() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let template = "{{ project_name }} {{ generated_at }} {{ name }}";
+        std::fs::write(tmp_dir.path().join("header.jinja"), template).unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_global("project_name", "quickform")
+            .with_global("generated_at", "2024-01-01")
+            .with_global("name", "global")
+            .render_operation("header.jinja", || async {
+                HashMap::from([
+                    ("name".to_string(), "Alice".to_string()),
+                    ("generated_at".to_string(), "today".to_string()),
+                ])
+            });
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["header.jinja"], "quickform today Alice");
+    }
+
+    #[tokio::test]
+    async fn test_run_incremental() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        let output_dir = tmp_dir.path().join("output");
+        std::fs::write(tmp_dir.path().join("user.jinja"), "{{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("age.jinja"), "{{ age }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .render_operation("user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            })
+            .render_operation("age.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
+
+        let summary = app.run_incremental(&output_dir).await.unwrap();
+        assert_eq!((summary.written, summary.skipped), (2, 0));
+
+        app.state.update(|u| u.age = 31).await;
+        let summary = app.run_incremental(&output_dir).await.unwrap();
+        assert_eq!((summary.written, summary.skipped), (1, 1));
+        assert_eq!(summary.to_string(), "1 of 2 files changed");
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("age.jinja")).unwrap(),
+            "31"
+        );
+    }
+
+    #[cfg(feature = "zip")]
     #[tokio::test]
-    async fn test_no_params() {
-        async fn get_default_name() -> HashMap<String, String> {
-            let mut map = HashMap::new();
-            map.insert("value".to_string(), "Default".to_string());
-            map
-        }
+    async fn test_from_zip() {
+        use std::io::Write as _;
+        use zip::write::SimpleFileOptions;
 
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        let template_path = tmp_dir.path().join("get_default.jinja");
-        std::fs::write(&template_path, "{{ value }}").unwrap();
+        let archive_path = tmp_dir.path().join("templates.zip");
+        let logo = vec![0x89, b'P', b'N', b'G', 0xff, 0x00];
+        {
+            let mut archive = zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+            let options = SimpleFileOptions::default();
+            archive.add_directory("models/", options).unwrap();
+            archive.start_file("models/user.jinja", options).unwrap();
+            archive.write_all(b"Hello, {{ name }}!").unwrap();
+            archive.start_file("assets/logo.png", options).unwrap();
+            archive.write_all(&logo).unwrap();
+            archive.finish().unwrap();
+        }
 
-        let app = App::from_dir(&tmp_dir.path())
-            .render_operation("get_default.jinja", get_default_name);
+        let app = App::from_zip(&archive_path)
+            .unwrap()
+            .with_state(User {
+                name: "Alice".to_string(),
+                age: 30,
+            })
+            .render_operation("models/user.jinja", |user: Data<User>| async move {
+                user.clone_inner().await
+            });
 
         let output_dir = tmp_dir.path().join("output");
         app.run(&output_dir).await.unwrap();
-        assert!(output_dir.join("get_default.jinja").exists());
-        assert_eq!(std::fs::read_to_string(output_dir.join("get_default.jinja")).unwrap(), "Default");
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("models/user.jinja")).unwrap(),
+            "Hello, Alice!"
+        );
+        assert_eq!(
+            std::fs::read(output_dir.join("assets/logo.png")).unwrap(),
+            logo
+        );
+        assert!(App::from_zip(tmp_dir.path().join("missing.zip")).is_err());
     }
 
     #[tokio::test]
-    async fn test_from_dir() {
-        async fn double_age(user: Data<User>) -> User {
-            let user = user.clone_inner().await;
-            User {
-                name: user.name,
-                age: user.age * 2,
-            }
-        }
+    async fn test_invalid_operation_output() {
+        struct Unserializable;
 
-        async fn codify_name(user: Data<User>) -> User {
-            let user = user.clone_inner().await;
-            let new_name = user
-                .name
-                .into_bytes()
-                .iter()
-                .map(|b| format!("{:02x}", b))
-                .collect::<Vec<String>>()
-                .join("-");
-            User {
-                name: new_name,
-                age: user.age,
+        impl Serialize for Unserializable {
+            fn serialize<S>(&self, _: S) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                Err(serde::ser::Error::custom(
+                    "connection handle cannot be serialized",
+                ))
             }
         }
 
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("plain.jinja"), "no variables").unwrap();
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation("plain.jinja", || async { Unserializable });
 
-        // Create child directory
-        let child_dir = tmp_dir.path().join("child");
-        std::fs::create_dir(&child_dir).unwrap();
-
-        let template_path_double_age = tmp_dir.path().join("double_age.jinja");
-        let template_path_codify_name = child_dir.join("codify_name.jinja");
+        let err = app.run_in_memory().await.unwrap_err();
+        let Error::OperationFailed {
+            operation, source, ..
+        } = err
+        else {
+            panic!("expected an operation failure, got {err:?}");
+        };
+        assert_eq!(operation, 0);
+        assert!(matches!(*source, Error::InvalidContext(_)));
+        assert!(source
+            .to_string()
+            .contains("connection handle cannot be serialized"));
+    }
 
-        std::fs::write(&template_path_double_age, "Age: {{ age }}").unwrap();
-        std::fs::write(&template_path_codify_name, "Name: {{ name }}").unwrap();
+    #[tokio::test]
+    async fn test_fs_extractor() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("user.jinja"),
+            "{{ name }}: {{ schema }}",
+        )
+        .unwrap();
+        std::fs::write(tmp_dir.path().join("count.jinja"), "{{ count }}").unwrap();
 
-        let app = App::from_dir(&tmp_dir.path())
+        let app = App::from_dir(tmp_dir.path())
             .with_state(User {
                 name: "Alice".to_string(),
                 age: 30,
             })
-            .render_operation("double_age.jinja", double_age)
-            .render_operation("child/codify_name.jinja", codify_name);
+            .json_operation("schema.json", |user: Data<User>| async move {
+                HashMap::from([("age".to_string(), user.clone_inner().await.age)])
+            })
+            .render_operation("user.jinja", |user: Data<User>, fs: Fs| async move {
+                HashMap::from([
+                    ("name".to_string(), user.clone_inner().await.name),
+                    (
+                        "schema".to_string(),
+                        fs.read_to_string("schema.json").unwrap(),
+                    ),
+                ])
+            })
+            .with_state(Config {
+                timeout: Duration::from_secs(10),
+            })
+            .render_operation(
+                "count.jinja",
+                |_: Data<User>, _: Data<Config>, fs: Fs| async move {
+                    let count = fs.with(|fs| fs.walk().len());
+                    HashMap::from([("count".to_string(), count)])
+                },
+            );
 
-        let output_dir = tmp_dir.path().join("output");
-        app.run(&output_dir).await.unwrap();
-        assert!(output_dir.join("double_age.jinja").exists());
-        assert_eq!(std::fs::read_to_string(output_dir.join("double_age.jinja")).unwrap(), "Age: 60");
-        assert!(output_dir.join("child/codify_name.jinja").exists());
-        assert_eq!(std::fs::read_to_string(output_dir.join("child/codify_name.jinja")).unwrap(), "Name: 41-6c-69-63-65");
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["user.jinja"], "Alice: {\n  \"age\": 30\n}");
+        assert_eq!(output["count.jinja"], "3");
     }
 
     #[tokio::test]
-    async fn test_multiple_params() {
-        async fn get_user_with_timeout(
-            user: Data<User>,
-            config: Data<Config>,
-        ) -> HashMap<String, String> {
-            let mut map = HashMap::new();
-            map.insert("user".to_string(), user.clone_inner().await.name);
-            map.insert("timeout".to_string(), config.clone_inner().await.timeout.as_secs().to_string());
-            map
+    async fn test_render_glob() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::create_dir(tmp_dir.path().join("pages")).unwrap();
+        for page in ["home", "about", "contact"] {
+            let path = tmp_dir.path().join(format!("pages/{page}.txt"));
+            std::fs::write(path, "{{ path }}").unwrap();
         }
+        std::fs::write(tmp_dir.path().join("index.txt"), "{{ path }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path()).render_glob(
+            "pages/*.txt",
+            |path: TemplatePath| async move {
+                HashMap::from([("path".to_string(), path.to_uppercase())])
+            },
+        );
+
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output.len(), 3);
+        assert_eq!(output["pages/home.txt"], "PAGES/HOME.TXT");
+        assert_eq!(output["pages/about.txt"], "PAGES/ABOUT.TXT");
+        assert_eq!(output["pages/contact.txt"], "PAGES/CONTACT.TXT");
+        assert!(!output.contains_key("index.txt"));
+    }
 
+    #[tokio::test]
+    async fn test_state_operation_fs() {
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        let template_path = tmp_dir.path().join("multiple_params.jinja");
-        std::fs::write(&template_path, "{{ timeout }} {{ user }}").unwrap();
+        std::fs::write(tmp_dir.path().join("a.txt"), "alpha").unwrap();
+        std::fs::write(tmp_dir.path().join("b.txt"), "beta").unwrap();
+        std::fs::write(tmp_dir.path().join("report.txt"), "{{ report }}").unwrap();
 
-        let app = App::from_dir(&tmp_dir.path())
-            .with_state(User {
-                name: "Bob".to_string(),
-                age: 25,
-            })
-            .with_state(Config {
-                timeout: Duration::from_secs(30),
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(String::new())
+            .render_operation("a.txt", |_: Data<String>| async {})
+            .render_operation("b.txt", |_: Data<String>| async {})
+            .state_operation(|report: Data<String>, fs: Fs| async move {
+                let sizes: Vec<String> = ["a.txt", "b.txt"]
+                    .iter()
+                    .map(|path| format!("{path}={}", fs.read_file(path).unwrap().len()))
+                    .collect();
+                fs.write_file("sizes.txt", sizes.join("\n")).unwrap();
+                report.update(|report| *report = sizes.join(", ")).await;
             })
-            .render_operation("multiple_params.jinja", get_user_with_timeout);
+            .render_operation("report.txt", |report: Data<String>| async move {
+                HashMap::from([("report".to_string(), report.clone_inner().await)])
+            });
 
         let output_dir = tmp_dir.path().join("output");
         app.run(&output_dir).await.unwrap();
-        assert!(output_dir.join("multiple_params.jinja").exists());
-        assert_eq!(std::fs::read_to_string(output_dir.join("multiple_params.jinja")).unwrap(), "30 Bob");
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("report.txt")).unwrap(),
+            "a.txt=5, b.txt=4"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("sizes.txt")).unwrap(),
+            "a.txt=5\nb.txt=4"
+        );
     }
 
     #[tokio::test]
-    async fn test_simple_params() {
-        async fn three_params(x: Data<i32>, y: Data<i32>, z: Data<i32>) -> HashMap<String, i32> {
-            let x = x.clone_inner().await;
-            let y = y.clone_inner().await;
-            let z = z.clone_inner().await;
-            let mut map = HashMap::new();
-            map.insert("sum".to_string(), x + y + z);
-            map
-        }
+    async fn test_run_timed() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("entities.txt"), "entities").unwrap();
+        std::fs::write(tmp_dir.path().join("relationships.txt"), "relationships").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation("entities.txt", || async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            })
+            .state_operation(|| async {})
+            .render_operation("relationships.txt", || async {});
+
+        let report = app.run_timed(tmp_dir.path().join("output")).await.unwrap();
+        let labels: Vec<_> = report
+            .timings
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect();
+        assert_eq!(
+            labels,
+            ["entities.txt", "state operation 1", "relationships.txt"]
+        );
+        assert_eq!(report.slowest().unwrap().0, "entities.txt");
+        assert!(report.timings[0].1 >= Duration::from_millis(50));
+        assert!(tmp_dir.path().join("output/relationships.txt").is_file());
+    }
 
+    #[tokio::test]
+    async fn test_labeled_state_operation() {
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        let template_path = tmp_dir.path().join("simple_params.jinja");
-        std::fs::write(&template_path, "{{ sum }}").unwrap();
+        let app = App::default()
+            .labeled_state_operation("fetch_entities", || async {})
+            .labeled_state_operation("summarize", |_: TemplatePath| async {});
 
-        let app = App::from_dir(&tmp_dir.path())
-            .with_state(1)
-            .with_state(2)
-            .with_state(3)
-            .render_operation("simple_params.jinja", three_params);
+        let err = app.run_timed(tmp_dir.path()).await.unwrap_err();
+        assert_eq!(err.to_string(), "Operation 'summarize' failed");
+        assert!(matches!(err, Error::OperationFailed { operation: 1, .. }));
+
+        let app = App::default().labeled_state_operation("fetch_entities", || async {});
+        let report = app.run_timed(tmp_dir.path()).await.unwrap();
+        assert_eq!(report.timings[0].0, "fetch_entities");
+    }
 
+    #[tokio::test]
+    async fn test_run_transactional() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "{{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation_to("user.jinja", "out/user.txt", || async {
+                HashMap::from([("name", "Alice")])
+            })
+            .try_state_operation(|| async { Err(std::io::Error::other("rate limited")) });
+
+        let before = app.output.get().fs.walk();
         let output_dir = tmp_dir.path().join("output");
-        app.run(&output_dir).await.unwrap();
-        assert!(output_dir.join("simple_params.jinja").exists());
-        assert_eq!(std::fs::read_to_string(output_dir.join("simple_params.jinja")).unwrap(), "6");
+        assert!(app.run_transactional(&output_dir).await.is_err());
+        assert_eq!(app.output.get().fs.walk(), before);
+        assert!(!output_dir.exists());
     }
 
     #[tokio::test]
-    async fn test_state_operation_single_state() {
-        let app = App::default()
-            .with_state(User {
-                name: "Alice".to_string(),
-                age: 30,
+    async fn test_render_one() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "{{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("other.jinja"), "{{ name }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation("user.jinja", || async {
+                HashMap::from([("name", "Alice")])
             })
-            .state_operation(|user: Data<User>| async move {
-                user.update(|u| u.name = "Bob".to_string()).await;
-            });
+            .render_operation("other.jinja", || async {
+                HashMap::from([("name", "Carol")])
+            })
+            .render_operation("user.jinja", || async { HashMap::from([("name", "Bob")]) });
 
-        // Run the app
+        let before = app.output.get().fs.walk();
+        assert_eq!(app.render_one("user.jinja").await.unwrap(), "Bob");
+        assert_eq!(app.output.get().fs.walk(), before);
+        assert!(matches!(
+            app.render_one("missing.jinja").await,
+            Err(Error::NoRenderOperation(path)) if path == "missing.jinja"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_binary_files_pass_through() {
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        app.run(tmp_dir.path()).await.unwrap();
+        let icon = [0x00, 0x00, 0x01, 0x00, 0xff, 0xfe, 0x80, 0x81];
+        std::fs::create_dir(tmp_dir.path().join("assets")).unwrap();
+        std::fs::write(tmp_dir.path().join("assets/favicon.ico"), icon).unwrap();
+        std::fs::write(tmp_dir.path().join("index.html"), "{{ title }}").unwrap();
 
-        // Verify the state was updated
+        let app = App::from_dir(tmp_dir.path()).render_operation("index.html", || async {
+            HashMap::from([("title", "Home")])
+        });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
         assert_eq!(
-            app.state.clone_inner().await.name,
-            "Bob"
+            std::fs::read(output_dir.join("assets/favicon.ico")).unwrap(),
+            icon
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("index.html")).unwrap(),
+            "Home"
         );
     }
 
     #[tokio::test]
-    async fn test_state_operation_multiple_states() {
-        let app = App::default()
-            .with_state(User {
-                name: "Alice".to_string(),
-                age: 30,
-            })
-            .with_state(Config {
-                timeout: Duration::from_secs(30),
-            })
-            .state_operation(|user: Data<User>, config: Data<Config>| async move {
-                user.update(|u| u.name = "Bob".to_string()).await;
-                config.update(|c| c.timeout = Duration::from_secs(60)).await;
+    async fn test_clear_operations() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("entities.txt"), "{{ count }}").unwrap();
+        std::fs::write(tmp_dir.path().join("summary.txt"), "{{ count }}").unwrap();
+
+        let mut app = App::from_dir(tmp_dir.path())
+            .with_state(0)
+            .state_operation(|count: Data<i32>| async move { count.update(|c| *c += 1).await })
+            .render_operation("entities.txt", |count: Data<i32>| async move {
+                HashMap::from([("count", count.clone_inner().await)])
             });
+        app.run_in_memory().await.unwrap();
+
+        app.clear_operations();
+        assert_eq!(app.operation_count(), 0);
+        let app = app.render_operation("summary.txt", |count: Data<i32>| async move {
+            HashMap::from([("count", count.clone_inner().await * 10)])
+        });
+
+        let output_dir = tmp_dir.path().join("output");
+        app.run(&output_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("entities.txt")).unwrap(),
+            "1"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("summary.txt")).unwrap(),
+            "10"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_stream() {
+        use futures::StreamExt;
 
-        // Run the app
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        app.run(tmp_dir.path()).await.unwrap();
+        std::fs::write(tmp_dir.path().join("a.txt"), "{{ step }}").unwrap();
+        std::fs::write(tmp_dir.path().join("b.txt"), "{{ step }}").unwrap();
 
-        // Verify both states were updated
+        let op =
+            |step: Data<i32>| async move { HashMap::from([("step", step.clone_inner().await)]) };
+        let app = App::from_dir(tmp_dir.path())
+            .with_state(1)
+            .render_operation("a.txt", op)
+            .state_operation(|step: Data<i32>| async move { step.update(|s| *s += 1).await })
+            .render_operation("b.txt", op)
+            .try_state_operation(|_: Data<i32>| async { Err(std::io::Error::other("failed")) })
+            .render_operation("c.txt", op);
+
+        let mut stream = std::pin::pin!(app.run_stream());
         assert_eq!(
-            app.state.0.clone_inner().await.name,
-            "Bob"
+            stream.next().await.unwrap().unwrap(),
+            ("a.txt".into(), "1".into())
         );
+        assert_eq!(app.state.clone_inner().await, 1);
         assert_eq!(
-            app.state.1.clone_inner().await.timeout,
-            Duration::from_secs(60)
+            stream.next().await.unwrap().unwrap(),
+            ("b.txt".into(), "2".into())
         );
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
     }
 
     #[tokio::test]
-    async fn test_state_operation_chain() {
-        let app = App::default()
-            .with_state(User {
-                name: "Alice".to_string(),
-                age: 30,
-            })
-            .state_operation(|user: Data<User>| async move {
-                user.update(|u| u.name = "Bob".to_string()).await;
+    async fn test_render_operation_with_timeout() {
+        let tmp_dir = tempdir::TempDir::new("test").unwrap();
+        std::fs::write(tmp_dir.path().join("fast.txt"), "{{ answer }}").unwrap();
+        std::fs::write(tmp_dir.path().join("slow.txt"), "{{ answer }}").unwrap();
+
+        let app = App::from_dir(tmp_dir.path())
+            .render_operation_with_timeout("fast.txt", Duration::from_secs(5), || async {
+                HashMap::from([("answer", 42)])
             })
-            .state_operation(|user: Data<User>| async move {
-                let current = user.clone_inner().await;
-                user.update(|u| u.name = format!("{}-modified", current.name)).await;
+            .render_operation_with_timeout("slow.txt", Duration::from_millis(20), || async {
+                std::future::pending::<()>().await;
             });
 
-        // Run the app
+        let err = app.run_in_memory().await.unwrap_err();
+        let Error::OperationFailed { source, .. } = err else {
+            panic!("expected an operation failure, got {err:?}");
+        };
+        assert!(matches!(
+            *source,
+            Error::OperationTimeout { ref label, duration }
+                if label == "slow.txt" && duration == Duration::from_millis(20)
+        ));
+        assert_eq!(app.output.get().fs.read_file("fast.txt").unwrap(), b"42");
+    }
+
+    #[tokio::test]
+    async fn test_with_shared_state() {
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        app.run(tmp_dir.path()).await.unwrap();
+        std::fs::write(tmp_dir.path().join("config.txt"), "{{ secs }}").unwrap();
 
-        // Verify the state was updated by both operations
-        assert_eq!(
-            app.state.clone_inner().await.name,
-            "Bob-modified"
-        );
+        let config = Data::new(Config {
+            timeout: Duration::from_secs(10),
+        });
+        let writer = App::from_dir(tmp_dir.path())
+            .with_shared_state(config.clone())
+            .state_operation(|config: Data<Config>| async move {
+                config.update(|c| c.timeout = Duration::from_secs(30)).await;
+            });
+        let reader = App::from_dir(tmp_dir.path())
+            .with_state(0)
+            .with_shared_state(config.clone())
+            .render_operation(
+                "config.txt",
+                |_: Data<i32>, config: Data<Config>| async move {
+                    HashMap::from([("secs", config.clone_inner().await.timeout.as_secs())])
+                },
+            );
+
+        writer.run_in_memory().await.unwrap();
+        assert_eq!(config.clone_inner().await.timeout, Duration::from_secs(30));
+        assert_eq!(reader.run_in_memory().await.unwrap()["config.txt"], "30");
     }
 
     #[tokio::test]
-    async fn test_mixed_operations() {
+    async fn test_map_state() {
         let tmp_dir = tempdir::TempDir::new("test").unwrap();
-        let template_path = tmp_dir.path().join("user.jinja");
-        std::fs::write(&template_path, "Name: {{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("user.jinja"), "{{ name }}").unwrap();
+        std::fs::write(tmp_dir.path().join("config.jinja"), "{{ timeout.secs }}").unwrap();
 
-        let app = App::from_dir(&tmp_dir.path())
+        let app = App::from_dir(tmp_dir.path())
             .with_state(User {
                 name: "Alice".to_string(),
                 age: 30,
             })
-            .state_operation(|user: Data<User>| async move {
-                user.update(|u| u.name = "Bob".to_string()).await;
-            })
             .render_operation("user.jinja", |user: Data<User>| async move {
                 user.clone_inner().await
+            })
+            .map_state(|user| Config {
+                timeout: Duration::from_secs(user.age.into()),
+            })
+            .render_operation("config.jinja", |config: Data<Config>| async move {
+                config.clone_inner().await
             });
 
-        let output_dir = tmp_dir.path().join("output");
-        app.run(&output_dir).await.unwrap();
+        let output = app.run_in_memory().await.unwrap();
+        assert_eq!(output["user.jinja"], "Alice");
+        assert_eq!(output["config.jinja"], "30");
 
-        // Verify the state was updated
-        assert_eq!(
-            app.state.clone_inner().await.name,
-            "Bob"
-        );
+        let app = app.replace_state(User {
+            name: "Bob".to_string(),
+            age: 40,
+        });
+        assert_eq!(app.state.clone_inner().await.name, "Bob");
 
-        // Verify the template was rendered with the updated state
-        assert_eq!(
-            std::fs::read_to_string(output_dir.join("user.jinja")).unwrap(),
-            "Name: Bob"
-        );
+        // State nothing else holds is moved instead of copied
+        struct Unique;
+        impl Clone for Unique {
+            fn clone(&self) -> Self {
+                panic!("unshared state was copied");
+            }
+        }
+        let app = App::default().with_state(Unique).map_state(|_| "mapped");
+        assert_eq!(app.state.clone_inner().await, "mapped");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "locked for writing")]
+    async fn test_map_state_locked() {
+        let app = App::default().with_state(1);
+        let state = app.state.clone();
+        let _guard = state.lock().await;
+        app.map_state(|count| count + 1);
     }
 }
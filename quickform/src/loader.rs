@@ -1,22 +1,22 @@
-use crate::fs::{FSError, MemFS};
+use crate::fs::{FSError, SharedMemFS};
 use minijinja::Error;
-use std::str;
+use std::sync::PoisonError;
 
 /// Creates a template loader that loads templates from the MemFS.
-pub fn memfs_loader(fs: MemFS) -> impl Fn(&str) -> Result<Option<String>, Error> {
+///
+/// The filesystem is read on every load, so changes made through the shared
+/// handle are picked up once the engine's template cache is cleared.
+pub fn memfs_loader(fs: SharedMemFS) -> impl Fn(&str) -> Result<Option<String>, Error> {
     move |name| {
-        match fs.read_file(name) {
-            Ok(content) => {
-                // Convert bytes to string
-                match str::from_utf8(content) {
-                    Ok(s) => Ok(Some(s.to_string())),
-                    Err(_) => Err(Error::new(
-                        minijinja::ErrorKind::InvalidOperation,
-                        "Template file contains invalid UTF-8",
-                    )),
-                }
-            }
-            Err(FSError::NotFound(_)) => Ok(None),
+        let fs = fs.read().unwrap_or_else(PoisonError::into_inner);
+        match fs.read_file_string(name) {
+            Ok(source) => Ok(Some(source)),
+            // Names escaping the root, e.g. with `..`, can't name a template
+            Err(FSError::NotFound(_) | FSError::InvalidPath) => Ok(None),
+            Err(FSError::InvalidUtf8(path)) => Err(Error::new(
+                minijinja::ErrorKind::InvalidOperation,
+                format!("Template file {path} contains invalid UTF-8"),
+            )),
             Err(e) => Err(Error::new(
                 minijinja::ErrorKind::InvalidOperation,
                 format!("Failed to load template: {}", e),
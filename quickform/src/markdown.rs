@@ -0,0 +1,72 @@
+//! Markdown-aware post-processing of rendered output
+//!
+//! When the `markdown` feature is enabled, an app can register a transform that
+//! receives the parsed [`pulldown_cmark`] event stream of every rendered
+//! Markdown file and returns a rewritten stream, which is serialized back to
+//! Markdown before the file is written. Typical uses are injecting a table of
+//! contents or rewriting links.
+//!
+//! A file is treated as Markdown when its output path, ignoring a trailing
+//! `.jinja` extension, ends in `.md` or `.markdown`.
+
+use std::sync::Arc;
+
+pub use pulldown_cmark;
+use pulldown_cmark::{Event, Parser};
+
+/// A user callback rewriting the Markdown events of a rendered file
+pub(crate) type MarkdownTransform =
+    Arc<dyn for<'a> Fn(&str, Vec<Event<'a>>) -> Vec<Event<'a>> + Send + Sync>;
+
+/// Whether an output path refers to a Markdown file
+pub(crate) fn is_markdown(output_path: &str) -> bool {
+    let path = output_path.strip_suffix(".jinja").unwrap_or(output_path);
+    path.ends_with(".md") || path.ends_with(".markdown")
+}
+
+/// Parses `source`, applies the transform and serializes the result back to Markdown
+pub(crate) fn transform(
+    output_path: &str,
+    source: &str,
+    transform: &MarkdownTransform,
+) -> std::io::Result<String> {
+    let events = transform(output_path, Parser::new(source).collect());
+    let mut output = String::with_capacity(source.len());
+    pulldown_cmark_to_cmark::cmark(events.iter(), &mut output).map_err(std::io::Error::other)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{HeadingLevel, Tag, TagEnd};
+
+    #[test]
+    fn test_is_markdown() {
+        assert!(is_markdown("README.md"));
+        assert!(is_markdown("docs/guide.markdown.jinja"));
+        assert!(!is_markdown("src/app.ts"));
+    }
+
+    #[test]
+    fn test_transform() {
+        let prepend_title: MarkdownTransform = Arc::new(|_, events| {
+            let mut out = vec![
+                Event::Start(Tag::Heading {
+                    level: HeadingLevel::H1,
+                    id: None,
+                    classes: Vec::new(),
+                    attrs: Vec::new(),
+                }),
+                Event::Text("Title".into()),
+                Event::End(TagEnd::Heading(HeadingLevel::H1)),
+            ];
+            out.extend(events);
+            out
+        });
+
+        let output = transform("doc.md", "Some *text*", &prepend_title).unwrap();
+        assert!(output.starts_with("# Title"));
+        assert!(output.ends_with("Some *text*"));
+    }
+}
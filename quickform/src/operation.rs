@@ -2,11 +2,11 @@
 //!
 //! This module provides the core traits and implementations for working with
 //! async functions in the QuickForm framework. It handles functions with
-//! different numbers of parameters (0 to 4) through macro-generated implementations.
+//! different numbers of parameters (0 to 8) through macro-generated implementations.
 //!
 //! # Examples
 //!
-//! ```rust
+//! ```rust,ignore
 //! use quickform::operation::*;
 //!
 //! // Example async function
@@ -19,23 +19,177 @@
 //! assert_eq!(result, "Hello, Alice!");
 //! ```
 
+use minijinja::Value;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
+use crate::concat::Separator;
 use crate::context::Context;
+use crate::state::Extractors;
+use crate::Error;
 
-// Operation that returns context for template rendering
-type BoxedRenderOperation =
-    Box<dyn Fn() -> Pin<Box<dyn Future<Output = Box<dyn Context>> + Send>> + Send + Sync>;
+// Operation that returns context for template rendering, shared so apps can be cloned
+pub(crate) type SharedRenderOperation = Arc<
+    dyn Fn(&Extractors) -> Pin<Box<dyn Future<Output = Result<Box<dyn Context>, Error>> + Send>>
+        + Send
+        + Sync,
+>;
 
 // Operation that only modifies state
-type BoxedStateOperation =
-    Box<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+pub(crate) type SharedStateOperation = Arc<
+    dyn Fn(&Extractors) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>> + Send + Sync,
+>;
+
+// Modifies state and returns (path, content) pairs of files to write
+pub(crate) type SharedEmittingOperation = Arc<
+    dyn Fn(&Extractors) -> Pin<Box<dyn Future<Output = Result<Vec<EmittedFile>, Error>> + Send>>
+        + Send
+        + Sync,
+>;
+
+// A file returned by an emitting state operation, as a (path, content) pair
+pub(crate) type EmittedFile = (String, Vec<u8>);
+
+// Produces the (output path, context) pair of every item of a collection
+pub(crate) type SharedItemsOperation = Arc<
+    dyn Fn() -> Pin<Box<dyn Future<Output = Result<Vec<(String, Value)>, Error>> + Send>>
+        + Send
+        + Sync,
+>;
 
 // Enum to store both types of operations
+#[derive(Clone)]
 pub enum OperationKind {
-    Render(String,BoxedRenderOperation), // Include template path
-    State(BoxedStateOperation),
+    Render {
+        template_path: String,
+        output_path: String,
+        op: SharedRenderOperation,
+    },
+    // Only modifies state, with an optional label for diagnostics
+    State {
+        label: Option<String>,
+        op: SharedStateOperation,
+    },
+    // Modifies state and returns files to write, whose paths are only known
+    // once it has run
+    Emitting {
+        op: SharedEmittingOperation,
+    },
+    // Output is stored under a name instead of being rendered
+    Named {
+        name: String,
+        op: SharedRenderOperation,
+    },
+    // Renders a template against the stored output of a named operation
+    Wired {
+        template_path: String,
+        name: String,
+    },
+    // Renders several templates from one output, as (template path, output path) pairs
+    Bundle {
+        outputs: Vec<(String, String)>,
+        op: SharedRenderOperation,
+    },
+    // Renders the first candidate template present when the operation runs
+    FirstAvailable {
+        candidates: Vec<String>,
+        output_path: String,
+        op: SharedRenderOperation,
+    },
+    // Renders several templates from one output and joins them into one file
+    Concatenated {
+        templates: Vec<String>,
+        output_path: String,
+        separator: Separator,
+        op: SharedRenderOperation,
+    },
+    // Serializes the operation's output instead of rendering a template
+    Serialized {
+        output_path: String,
+        format: OutputFormat,
+        op: SharedRenderOperation,
+    },
+    // Renders a template once per item of a collection state
+    PerItem {
+        template_path: String,
+        items: SharedItemsOperation,
+    },
+}
+
+// Data format the output of a serialized operation is written in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum OutputFormat {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl OperationKind {
+    /// Returns whether this is a plain render operation
+    pub(crate) fn is_render(&self) -> bool {
+        matches!(self, OperationKind::Render { .. })
+    }
+
+    /// Returns a human-readable label for the operation at `index`
+    pub(crate) fn label(&self, index: usize) -> String {
+        match self {
+            OperationKind::Render { template_path, .. }
+            | OperationKind::Wired { template_path, .. }
+            | OperationKind::PerItem { template_path, .. } => template_path.clone(),
+            OperationKind::FirstAvailable { output_path, .. }
+            | OperationKind::Concatenated { output_path, .. }
+            | OperationKind::Serialized { output_path, .. } => output_path.clone(),
+            OperationKind::Named { name, .. } => name.clone(),
+            OperationKind::Bundle { outputs, .. } => outputs
+                .iter()
+                .map(|(template, _)| template.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            OperationKind::State {
+                label: Some(label), ..
+            } => label.clone(),
+            OperationKind::State { label: None, .. } => format!("state operation {index}"),
+            OperationKind::Emitting { .. } => format!("emitting state operation {index}"),
+        }
+    }
+
+    /// Returns the paths of the files the operation renders
+    ///
+    /// Paths are those the operation was registered with, before any
+    /// collision handling. Per-item paths depend on the collection, and paths
+    /// of emitting state operations on what they return, so neither is
+    /// included; see [`OperationKind::dynamic_output`].
+    pub(crate) fn output_paths(&self) -> Vec<&str> {
+        match self {
+            OperationKind::Render { output_path, .. }
+            | OperationKind::FirstAvailable { output_path, .. }
+            | OperationKind::Concatenated { output_path, .. }
+            | OperationKind::Serialized { output_path, .. } => vec![output_path],
+            OperationKind::Wired { template_path, .. } => vec![template_path],
+            OperationKind::Bundle { outputs, .. } => {
+                outputs.iter().map(|(_, output)| output.as_str()).collect()
+            }
+            OperationKind::State { .. }
+            | OperationKind::Emitting { .. }
+            | OperationKind::Named { .. }
+            | OperationKind::PerItem { .. } => Vec::new(),
+        }
+    }
+
+    /// Returns a marker standing for the outputs of the operation at `index`
+    /// if their paths are only known once it runs
+    ///
+    /// The marker has the form `<dynamic: label>`, where `label` is the
+    /// operation's [`OperationKind::label`].
+    pub(crate) fn dynamic_output(&self, index: usize) -> Option<String> {
+        match self {
+            OperationKind::PerItem { .. } | OperationKind::Emitting { .. } => {
+                Some(format!("<dynamic: {}>", self.label(index)))
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Defines the signature of a function, including its parameter and output types
@@ -54,9 +208,63 @@ pub trait FunctionSignature {
 /// This trait is implemented for closures and functions that match
 /// the signature defined by a `FunctionSignature`.
 ///
+/// # Closures
+///
+/// Besides `async fn`s, an operation can be any `Fn` closure returning a
+/// future, including one capturing values from its environment. Operations
+/// run once per run, and possibly more often, so the closure must stay
+/// callable: it can't move what it captures into the future it returns.
+/// Clone captured values, such as a `Data` shared with code outside the app,
+/// before the `async move` block instead:
+///
+/// ```rust
+/// use quickform::{state::Data, App};
+///
+/// #[derive(Clone)]
+/// struct Config {
+///     greeting: String,
+/// }
+///
+/// let config = Data::new(Config { greeting: "Hello".to_string() });
+/// let app = App::default()
+///     .with_state("Alice".to_string())
+///     .render_operation("greet.txt", move |name: Data<String>| {
+///         let config = config.clone();
+///         async move {
+///             let greeting = config.read().await.greeting.clone();
+///             format!("{greeting}, {}!", *name.read().await)
+///         }
+///     });
+/// ```
+///
+/// Moving the captured value into the future makes the closure `FnOnce`,
+/// which is not an operation:
+///
+/// ```compile_fail
+/// use quickform::{state::Data, App};
+///
+/// let config = Data::new(String::from("Hello"));
+/// let app = App::default()
+///     .with_state("Alice".to_string())
+///     .render_operation("greet.txt", move |name: Data<String>| async move {
+///         let greeting = config.clone_inner().await;
+///         format!("{greeting}, {}!", *name.read().await)
+///     });
+/// ```
+///
+/// Closure parameters need type annotations, e.g. `|name: Data<String>|`,
+/// as they can't be inferred from the app's state.
+///
 /// # Type Parameters
 ///
 /// * `F` - The function signature this operation implements
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid operation for this app",
+    label = "invalid operation",
+    note = "operations are async functions, or closures returning a future",
+    note = "they must take every state added with `with_state`, in the same order, \
+            each wrapped in `Data<_>`, optionally followed by extractors such as `Fs`"
+)]
 pub trait Operation<F: FunctionSignature> {
     /// The future type returned by this operation
     type Future: Future<Output = F::Output>;
@@ -148,6 +356,27 @@ impl_function_traits!((T1, p1)); // 1 parameter
 impl_function_traits!((T1, p1), (T2, p2)); // 2 parameters
 impl_function_traits!((T1, p1), (T2, p2), (T3, p3)); // 3 parameters
 impl_function_traits!((T1, p1), (T2, p2), (T3, p3), (T4, p4)); // 4 parameters
+impl_function_traits!((T1, p1), (T2, p2), (T3, p3), (T4, p4), (T5, p5)); // 5 parameters
+impl_function_traits!((T1, p1), (T2, p2), (T3, p3), (T4, p4), (T5, p5), (T6, p6)); // 6 parameters
+impl_function_traits!(
+    (T1, p1),
+    (T2, p2),
+    (T3, p3),
+    (T4, p4),
+    (T5, p5),
+    (T6, p6),
+    (T7, p7)
+); // 7 parameters
+impl_function_traits!(
+    (T1, p1),
+    (T2, p2),
+    (T3, p3),
+    (T4, p4),
+    (T5, p5),
+    (T6, p6),
+    (T7, p7),
+    (T8, p8)
+); // 8 parameters
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,43 @@
+//! Timing of the operations executed by a run
+//!
+//! [`App::run_timed`](crate::App::run_timed) measures how long each operation
+//! takes, which shows where a pipeline of slow, e.g. LLM-backed, operations
+//! spends its time.
+
+use std::fmt;
+use std::time::Duration;
+
+/// How long each operation of a run took, in execution order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunReport {
+    /// The label of each operation and how long it took
+    ///
+    /// Render operations are labelled with their template path, named
+    /// operations with their name, and state operations with the label they
+    /// were registered with, or else their index.
+    pub timings: Vec<(String, Duration)>,
+}
+
+impl RunReport {
+    /// Returns the combined duration of every operation
+    pub fn total(&self) -> Duration {
+        self.timings.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Returns the label and duration of the operation that took longest
+    pub fn slowest(&self) -> Option<(&str, Duration)> {
+        self.timings
+            .iter()
+            .max_by_key(|(_, duration)| *duration)
+            .map(|(label, duration)| (label.as_str(), *duration))
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, duration) in &self.timings {
+            writeln!(f, "{label} took {duration:.2?}")?;
+        }
+        write!(f, "total {:.2?}", self.total())
+    }
+}
@@ -0,0 +1,101 @@
+//! Retrying of operations that fail transiently
+//!
+//! Operations calling external APIs can fail for reasons that go away on their
+//! own. A [`RetryPolicy`] passed to
+//! [`App::render_operation_with_retry`](crate::App::render_operation_with_retry)
+//! runs such an operation again, waiting longer after each failure.
+
+use std::time::Duration;
+
+/// How often to retry a failing operation and how long to wait in between
+///
+/// The wait after the first failure is the initial backoff, and each later
+/// wait is the previous one multiplied by the backoff factor. The defaults
+/// are 3 attempts, an initial backoff of 100 milliseconds and a factor of 2.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use quickform::RetryPolicy;
+///
+/// // Waits 1s, then 2s, then 4s before giving up after the fourth attempt
+/// let policy = RetryPolicy::new(4).with_backoff(Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    factor: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(100),
+            factor: 2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy running an operation at most `max_attempts` times
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - How many times the operation may run in total,
+    ///   including the first attempt. Values below 1 are treated as 1.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// Sets how long to wait after the first failure
+    ///
+    /// # Arguments
+    ///
+    /// * `backoff` - The wait before the second attempt
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets what each wait is multiplied by to get the next one
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - The multiplier; 1 waits the same time before each retry
+    pub fn with_factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Returns how many times the operation may run in total
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns how long to wait after the given failed attempt, counted from 1
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let multiplier = self.factor.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(multiplier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::new(0).with_backoff(Duration::from_millis(10));
+        assert_eq!(policy.max_attempts(), 1);
+        assert_eq!(policy.delay(1), Duration::from_millis(10));
+        assert_eq!(policy.delay(3), Duration::from_millis(40));
+        assert_eq!(policy.with_factor(1).delay(3), Duration::from_millis(10));
+        assert_eq!(policy.delay(100), Duration::from_millis(10) * u32::MAX);
+    }
+}
@@ -0,0 +1,101 @@
+//! Output of an app, kept per clone and per run
+//!
+//! Every run renders into its own copy of the app's output, which replaces
+//! the app's output once the run finishes. Runs of an app and of its clones
+//! therefore never see each other's files or named outputs.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockWriteGuard};
+
+use minijinja::Value;
+
+use crate::fs::{MemFS, SharedMemFS};
+
+/// What the runs of an app have rendered so far
+#[derive(Clone, Default)]
+pub(crate) struct Output {
+    /// Templates and rendered files
+    pub(crate) fs: MemFS,
+    /// Outputs of the named operations that ran
+    pub(crate) named_outputs: HashMap<String, Value>,
+}
+
+/// The output of an app, which each clone keeps to itself
+///
+/// Cloning doesn't copy the output until one of the clones changes it.
+#[derive(Default)]
+pub(crate) struct OutputSlot(RwLock<Arc<Output>>);
+
+impl Clone for OutputSlot {
+    fn clone(&self) -> Self {
+        Self(RwLock::new(self.get()))
+    }
+}
+
+impl OutputSlot {
+    /// Creates an output holding the files of `fs`
+    pub(crate) fn new(fs: MemFS) -> Self {
+        Self(RwLock::new(Arc::new(Output {
+            fs,
+            named_outputs: HashMap::new(),
+        })))
+    }
+
+    /// Returns the current output
+    pub(crate) fn get(&self) -> Arc<Output> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the output with what a run rendered
+    pub(crate) fn set(&self, output: Arc<Output>) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = output;
+    }
+
+    /// Returns the output for editing, copying it first if clones share it
+    pub(crate) fn make_mut(&mut self) -> &mut Output {
+        Arc::make_mut(self.0.get_mut().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// The output a single run renders into
+pub(crate) struct Run {
+    pub(crate) fs: SharedMemFS,
+    pub(crate) named_outputs: Arc<RwLock<HashMap<String, Value>>>,
+    /// Paths claimed under the collision policy by the run's operations
+    pub(crate) written_paths: Mutex<HashSet<String>>,
+}
+
+impl Run {
+    /// Starts a run from a copy of an app's output
+    pub(crate) fn new(output: &Output) -> Self {
+        Self {
+            fs: Arc::new(RwLock::new(output.fs.clone())),
+            named_outputs: Arc::new(RwLock::new(output.named_outputs.clone())),
+            written_paths: Mutex::default(),
+        }
+    }
+
+    /// Returns the files rendered so far for writing
+    pub(crate) fn fs_mut(&self) -> RwLockWriteGuard<'_, MemFS> {
+        self.fs.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Ends the run, returning what it rendered
+    ///
+    /// Extractors handed to operations may still hold the run's MemFS, so
+    /// its contents are moved out rather than the MemFS itself.
+    pub(crate) fn into_output(self) -> Output {
+        Output {
+            fs: std::mem::take(&mut *self.fs_mut()),
+            named_outputs: std::mem::take(
+                &mut *self
+                    .named_outputs
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner),
+            ),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Step-by-step execution of an app's operations
+//!
+//! A [`Runner`] executes the operations registered on an [`App`] one at a time,
+//! so callers can inspect state and rendered output between steps. State
+//! persists across steps, exactly as it does during [`App::run`].
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! let mut runner = app.into_runner();
+//! while let Some(step) = runner.step().await {
+//!     match step? {
+//!         StepResult::Rendered { path, content } => println!("{path}:\n{content}"),
+//!         StepResult::State => println!("state updated"),
+//!         StepResult::Emitted { files } => println!("emitted {} files", files.len()),
+//!         StepResult::Named { name } => println!("stored output of {name}"),
+//!         StepResult::Bundle { files } | StepResult::Items { files } => {
+//!             println!("rendered {} files", files.len())
+//!         }
+//!     }
+//! }
+//! runner.finish("output/")?;
+//! ```
+
+use std::path::Path;
+
+use crate::run::Run;
+use crate::{App, Result};
+
+/// The outcome of executing a single operation
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    /// A render operation wrote `content` to `path` in the MemFS
+    Rendered { path: String, content: String },
+    /// A state operation ran and possibly mutated the app's state
+    State,
+    /// An emitting state operation wrote each `(path, content)` pair it
+    /// returned to the MemFS
+    Emitted { files: Vec<(String, Vec<u8>)> },
+    /// A named operation stored its output under `name`
+    Named { name: String },
+    /// A bundle operation wrote each `(path, content)` pair to the MemFS
+    Bundle { files: Vec<(String, String)> },
+    /// A per-item operation wrote one `(path, content)` pair per item
+    Items { files: Vec<(String, String)> },
+}
+
+impl StepResult {
+    /// Returns the `(path, content)` pairs the step wrote to the MemFS
+    ///
+    /// Content of files emitted by a state operation that is not valid UTF-8
+    /// is converted lossily, with invalid sequences replaced by `U+FFFD`.
+    pub fn into_files(self) -> Vec<(String, String)> {
+        match self {
+            StepResult::Rendered { path, content } => vec![(path, content)],
+            StepResult::Bundle { files } | StepResult::Items { files } => files,
+            StepResult::Emitted { files } => files
+                .into_iter()
+                .map(|(path, content)| (path, String::from_utf8_lossy(&content).into_owned()))
+                .collect(),
+            StepResult::State | StepResult::Named { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Executes an app's operations one at a time
+///
+/// Created with [`App::into_runner`].
+///
+/// # Type Parameters
+///
+/// * `T` - The type of state stored in the App
+pub struct Runner<T> {
+    app: App<T>,
+    run: Run,
+    next: usize,
+}
+
+impl<T: Send + Sync + Clone + 'static> Runner<T> {
+    /// Creates a runner positioned before the app's first operation
+    pub(crate) fn new(app: App<T>) -> Self {
+        let run = app.begin_run();
+        Self { app, run, next: 0 }
+    }
+
+    /// Executes the next operation
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Result<StepResult>>` - What the operation did, or `None` once
+    ///   every operation has been executed
+    pub async fn step(&mut self) -> Option<Result<StepResult>> {
+        let index = self.next;
+        let operation = self.app.operations.get(index)?;
+        self.next += 1;
+        Some(self.app.execute(&self.run, index, operation).await)
+    }
+
+    /// Returns the number of operations that have not been executed yet
+    pub fn remaining(&self) -> usize {
+        self.app.operations.len() - self.next
+    }
+
+    /// Returns the app's state for inspection between steps
+    pub fn state(&self) -> &T {
+        &self.app.state
+    }
+
+    /// Writes everything rendered so far to disk
+    ///
+    /// # Arguments
+    ///
+    /// * `output_dir` - The directory to write the MemFS to
+    pub fn finish<P: AsRef<Path>>(&self, output_dir: P) -> Result<()> {
+        self.app.write_output(&self.run, output_dir)
+    }
+
+    /// Converts the runner back into the app it was created from
+    ///
+    /// Everything rendered so far becomes the app's output.
+    pub fn into_app(self) -> App<T> {
+        self.app.finish_run(self.run);
+        self.app
+    }
+}
@@ -3,7 +3,7 @@
 //! This module provides types and traits for managing mutable application state in a thread-safe
 //! and type-safe manner. It includes wrapper types for state data and traits for
 //! converting state into function parameters. The state can be safely modified across
-//! different tasks using Tokio's async mutex.
+//! different tasks using Tokio's async read-write lock.
 //!
 //! # Examples
 //!
@@ -29,21 +29,26 @@
 //! };
 //! ```
 
+use crate::fs::SharedMemFS;
 use crate::operation::FunctionSignature;
-use std::ops::Deref;
+use crate::Error;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{OwnedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// Thread-safe wrapper for mutable state data
 ///
-/// Wraps any type T in an Arc<Mutex> for thread-safe mutable access.
+/// Wraps any type T in an Arc<RwLock> for thread-safe mutable access.
 /// Provides an ergonomic API for accessing and modifying the state without
 /// directly handling locks.
 ///
 /// # Type Parameters
 ///
 /// * `T` - The type of state being wrapped
-pub struct Data<T>(Arc<Mutex<T>>);
+pub struct Data<T>(Arc<RwLock<T>>);
 
 impl<T> Data<T> {
     /// Creates a new `Data` instance wrapping the provided state
@@ -55,10 +60,11 @@ impl<T> Data<T> {
     /// # Examples
     ///
     /// ```rust
+    /// # use quickform::state::Data;
     /// let state = Data::new(String::from("hello"));
     /// ```
     pub fn new(state: T) -> Data<T> {
-        Data(Arc::new(Mutex::new(state)))
+        Data(Arc::new(RwLock::new(state)))
     }
 
     /// Gets a clone of the current state value
@@ -70,6 +76,7 @@ impl<T> Data<T> {
     /// # Examples
     ///
     /// ```rust
+    /// # use quickform::state::Data;
     /// let state = Data::new(String::from("hello"));
     /// async {
     ///     let value = state.clone_inner().await;
@@ -80,7 +87,36 @@ impl<T> Data<T> {
     where
         T: Clone,
     {
-        self.0.lock().await.clone()
+        self.read().await.get().clone()
+    }
+
+    /// Computes a value from a shared reference to the state
+    ///
+    /// The state is read-locked only for the duration of the closure, so a
+    /// single field can be read without cloning the whole value.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A closure that receives a reference to the state
+    ///
+    /// # Returns
+    ///
+    /// The value returned by the closure
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let state = Data::new(vec![1, 2, 3]);
+    /// async {
+    ///     assert_eq!(state.with(|items| items.len()).await, 3);
+    /// };
+    /// ```
+    pub async fn with<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(self.read().await.get())
     }
 
     /// Updates the state using a closure
@@ -92,6 +128,7 @@ impl<T> Data<T> {
     /// # Examples
     ///
     /// ```rust
+    /// # use quickform::state::Data;
     /// let state = Data::new(String::from("hello"));
     /// async {
     ///     state.update(|s| s.push_str(" world")).await;
@@ -102,8 +139,43 @@ impl<T> Data<T> {
     where
         F: FnOnce(&mut T),
     {
-        let mut lock = self.0.lock().await;
-        f(&mut *lock);
+        f(self.lock().await.get_mut());
+    }
+
+    /// Updates the state using a closure that can fail
+    ///
+    /// The closure's error is returned to the caller. Changes it made before
+    /// failing are kept, so it should validate before mutating the state.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A closure that receives a mutable reference to the state
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), E>` - The closure's result
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let state = Data::new(10u32);
+    /// async {
+    ///     let result = state
+    ///         .try_update(|n| {
+    ///             *n = n.checked_sub(20).ok_or("underflow")?;
+    ///             Ok(())
+    ///         })
+    ///         .await;
+    ///     assert_eq!(result, Err("underflow"));
+    ///     assert_eq!(state.clone_inner().await, 10);
+    /// };
+    /// ```
+    pub async fn try_update<E, F>(&self, f: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut T) -> Result<(), E>,
+    {
+        f(self.lock().await.get_mut())
     }
 
     /// Sets the state to a new value
@@ -115,6 +187,7 @@ impl<T> Data<T> {
     /// # Examples
     ///
     /// ```rust
+    /// # use quickform::state::Data;
     /// let state = Data::new(String::from("hello"));
     /// async {
     ///     state.set(String::from("world")).await;
@@ -122,17 +195,267 @@ impl<T> Data<T> {
     /// };
     /// ```
     pub async fn set(&self, new_state: T) {
-        *self.0.lock().await = new_state;
+        self.lock().await.set(new_state);
+    }
+
+    /// Sets the state to a new value, returning the previous one
+    ///
+    /// # Arguments
+    ///
+    /// * `new_state` - The new state value
+    ///
+    /// # Returns
+    ///
+    /// The state value before the replacement
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let log = Data::new(vec![String::from("started")]);
+    /// async {
+    ///     let entries = log.replace(Vec::new()).await;
+    ///     assert_eq!(entries, ["started"]);
+    /// };
+    /// ```
+    pub async fn replace(&self, new_state: T) -> T {
+        std::mem::replace(self.lock().await.get_mut(), new_state)
     }
 
-    /// Unwraps the Data wrapper, returning the internal Arc<Mutex>
+    /// Sets the state to a new value, but only if it equals `expected`
+    ///
+    /// The comparison and the write happen under a single lock, so no other
+    /// update can land in between. Concurrent operations can use this for
+    /// optimistic updates: read the state, compute a new value, and retry
+    /// from the returned value if another operation changed it meanwhile.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - The value the state must currently have
+    /// * `new_state` - The new state value
     ///
     /// # Returns
     ///
-    /// The underlying Arc<Mutex<T>>
-    pub fn into_inner(self) -> Arc<Mutex<T>> {
+    /// `Ok` if the state was updated, or else the current state value as
+    /// `Err`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let version = Data::new(1);
+    /// async {
+    ///     assert_eq!(version.compare_and_swap(&1, 2).await, Ok(()));
+    ///     assert_eq!(version.compare_and_swap(&1, 3).await, Err(2));
+    /// };
+    /// ```
+    pub async fn compare_and_swap(&self, expected: &T, new_state: T) -> Result<(), T>
+    where
+        T: PartialEq + Clone,
+    {
+        let mut guard = self.lock().await;
+        if *guard != *expected {
+            return Err(guard.clone());
+        }
+        guard.set(new_state);
+        Ok(())
+    }
+
+    /// Locks the state, returning a guard for a sequence of reads and writes
+    ///
+    /// The lock is held until the guard is dropped, so several steps can be
+    /// applied atomically without re-locking in between. This is the
+    /// primitive the other accessors are built on.
+    ///
+    /// Holding the guard across an `.await` blocks every other access to the
+    /// state in the meantime, and deadlocks if the same task tries to access
+    /// the state again (for example by calling `update`) while holding it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let state = Data::new(vec![1, 2]);
+    /// async {
+    ///     let mut guard = state.lock().await;
+    ///     let total: i32 = guard.get().iter().sum();
+    ///     guard.set(vec![total]);
+    /// };
+    /// ```
+    pub async fn lock(&self) -> DataGuard<'_, T> {
+        DataGuard(self.0.write().await)
+    }
+
+    /// Locks the state for writing, returning a guard that owns the lock
+    ///
+    /// Unlike [`Data::lock`], the guard does not borrow the `Data`, so it can
+    /// be moved into spawned tasks or stored alongside other owned values
+    /// while state is mutated incrementally, for example when streaming
+    /// tokens into a buffer across several `.await`s.
+    ///
+    /// The state stays locked for as long as the guard lives, which makes
+    /// deadlocks easy to introduce:
+    ///
+    /// - Accessing the same state again from the task holding the guard, for
+    ///   example with `update`, `read` or `clone_inner`, never completes.
+    /// - During a run, every later operation taking this state waits for the
+    ///   guard to be dropped. A guard kept alive after its operation returns,
+    ///   e.g. in a spawned task waiting on the run, stalls the run forever.
+    /// - Under [`App::run_parallel`](crate::App::run_parallel), concurrent
+    ///   operations taking this state wait for each other.
+    ///
+    /// Drop the guard as soon as the incremental update is done.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let buffer = Data::new(String::new());
+    /// async {
+    ///     let mut guard = buffer.lock_owned().await;
+    ///     for token in ["Hello", ", ", "world"] {
+    ///         tokio::task::yield_now().await;
+    ///         guard.push_str(token);
+    ///     }
+    /// };
+    /// ```
+    pub async fn lock_owned(&self) -> OwnedDataGuard<T> {
+        OwnedDataGuard(self.0.clone().write_owned().await)
+    }
+
+    /// Locks the state for reading, returning a guard shared with other readers
+    ///
+    /// Any number of read guards can be held at once, so operations that only
+    /// read the state do not wait for each other. Writers (`lock`, `update`
+    /// and `set`) wait until every read guard has been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use quickform::state::Data;
+    /// let state = Data::new(vec![1, 2]);
+    /// async {
+    ///     let first = state.read().await;
+    ///     let second = state.read().await;
+    ///     assert_eq!(first.len(), second.len());
+    /// };
+    /// ```
+    pub async fn read(&self) -> DataReadGuard<'_, T> {
+        DataReadGuard(self.0.read().await)
+    }
+
+    /// Unwraps the Data wrapper, returning the internal Arc<RwLock>
+    ///
+    /// # Returns
+    ///
+    /// The underlying Arc<RwLock<T>>
+    pub fn into_rwlock(self) -> Arc<RwLock<T>> {
         self.0
     }
+
+    /// Unwraps the Data wrapper, returning the internal Arc<RwLock>
+    ///
+    /// # Returns
+    ///
+    /// The underlying Arc<RwLock<T>>
+    #[deprecated(note = "`Data` is backed by an `RwLock`; use `Data::into_rwlock`")]
+    pub fn into_inner(self) -> Arc<RwLock<T>> {
+        self.into_rwlock()
+    }
+}
+
+/// Exclusive access to the state of a [`Data`], returned by [`Data::lock`]
+///
+/// The state stays locked until the guard is dropped.
+pub struct DataGuard<'a, T>(RwLockWriteGuard<'a, T>);
+
+impl<T> DataGuard<'_, T> {
+    /// Returns a reference to the state
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the state
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Replaces the state with a new value
+    pub fn set(&mut self, value: T) {
+        *self.0 = value;
+    }
+}
+
+impl<T> Deref for DataGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for DataGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Exclusive access to the state of a [`Data`], returned by
+/// [`Data::lock_owned`]
+///
+/// Like [`DataGuard`], but owns its lock instead of borrowing the `Data`. The
+/// state stays locked until the guard is dropped.
+pub struct OwnedDataGuard<T>(OwnedRwLockWriteGuard<T>);
+
+impl<T> OwnedDataGuard<T> {
+    /// Returns a reference to the state
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the state
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Replaces the state with a new value
+    pub fn set(&mut self, value: T) {
+        *self.0 = value;
+    }
+}
+
+impl<T> Deref for OwnedDataGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for OwnedDataGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Shared access to the state of a [`Data`], returned by [`Data::read`]
+///
+/// The state cannot be written until the guard is dropped.
+pub struct DataReadGuard<'a, T>(RwLockReadGuard<'a, T>);
+
+impl<T> DataReadGuard<'_, T> {
+    /// Returns a reference to the state
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Deref for DataReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
 }
 
 /// Implements [Deref] to allow transparent access to the underlying [Arc]
@@ -140,9 +463,9 @@ impl<T> Data<T> {
 /// This implementation enables using methods from [Arc] directly on `Data<T>` instances
 /// through deref coercion.
 impl<T> Deref for Data<T> {
-    type Target = Arc<Mutex<T>>;
+    type Target = Arc<RwLock<T>>;
 
-    fn deref(&self) -> &Arc<Mutex<T>> {
+    fn deref(&self) -> &Arc<RwLock<T>> {
         &self.0
     }
 }
@@ -157,12 +480,12 @@ impl<T> Clone for Data<T> {
     }
 }
 
-/// Implements conversion from Arc<Mutex> to `Data<T>`
+/// Implements conversion from Arc<RwLock> to `Data<T>`
 ///
-/// This allows creating a `Data<T>` instance from an existing Arc<Mutex>,
-/// which is useful when integrating with other code that uses Arc<Mutex> directly.
-impl<T> From<Arc<Mutex<T>>> for Data<T> {
-    fn from(arc: Arc<Mutex<T>>) -> Self {
+/// This allows creating a `Data<T>` instance from an existing Arc<RwLock>,
+/// which is useful when integrating with other code that uses Arc<RwLock> directly.
+impl<T> From<Arc<RwLock<T>>> for Data<T> {
+    fn from(arc: Arc<RwLock<T>>) -> Self {
         Data(arc)
     }
 }
@@ -173,17 +496,201 @@ impl<T> From<Arc<Mutex<T>>> for Data<T> {
 #[derive(Default, Clone)]
 pub struct NoData;
 
+/// Values added with `App::with_shared`, keyed by their type
+pub(crate) type SharedValues = Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>;
+
+/// What the app makes available to extractor parameters of an operation
+///
+/// Built by the app each time an operation runs and passed to
+/// [`IntoFunctionParams::into_params`].
+#[derive(Clone, Default)]
+pub struct Extractors {
+    pub(crate) fs: SharedMemFS,
+    pub(crate) template_path: Option<String>,
+    pub(crate) shared: SharedValues,
+}
+
+impl Extractors {
+    /// Returns a copy that also provides the template being rendered
+    pub(crate) fn for_template(&self, template_path: &str) -> Extractors {
+        Extractors {
+            template_path: Some(template_path.to_string()),
+            ..self.clone()
+        }
+    }
+}
+
+/// A parameter type an operation can receive besides its state
+///
+/// Extractor parameters follow the state parameters in an operation's
+/// signature, e.g. `async fn op(user: Data<User>, fs: Fs)`, and are built
+/// fresh every time the operation runs. Up to three extractors can be taken,
+/// as long as the operation has at most 8 parameters in total.
+pub trait Extractor: Sized {
+    /// Builds the parameter for the operation being run
+    ///
+    /// # Arguments
+    ///
+    /// * `extractors` - What the app makes available to the operation
+    fn extract(extractors: &Extractors) -> Result<Self, Error>;
+}
+
+/// The path of the template an operation renders, as an operation parameter
+///
+/// Mostly useful to operations registered for several templates at once,
+/// such as with [`App::render_glob`](crate::App::render_glob). Only
+/// available to render operations with a single template; other operations
+/// fail with [`Error::ExtractorUnavailable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplatePath(String);
+
+impl TemplatePath {
+    /// Returns the template path as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for TemplatePath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Extractor for TemplatePath {
+    fn extract(extractors: &Extractors) -> Result<Self, Error> {
+        extractors
+            .template_path
+            .clone()
+            .map(TemplatePath)
+            .ok_or(Error::ExtractorUnavailable("TemplatePath"))
+    }
+}
+
+/// Immutable data shared by operations without locking, as an operation
+/// parameter
+///
+/// Values are added with [`App::with_shared`](crate::App::with_shared) and
+/// can never be modified afterwards, so unlike [`Data`] they are read
+/// directly, without a lock. Useful for large read-only inputs, such as a
+/// schema consumed by many operations. Like other extractors, `Shared`
+/// parameters follow an operation's state parameters; operations of an app
+/// without a shared value of the requested type fail with
+/// [`Error::ExtractorUnavailable`].
+///
+/// # Examples
+///
+/// ```rust
+/// use quickform::{state::{Data, Shared}, App};
+///
+/// struct Schema {
+///     tables: Vec<String>,
+/// }
+///
+/// async fn tables(prefix: Data<String>, schema: Shared<Schema>) -> Vec<String> {
+///     let prefix = prefix.clone_inner().await;
+///     schema.tables.iter().map(|table| format!("{prefix}{table}")).collect()
+/// }
+///
+/// let app = App::default()
+///     .with_state(String::from("app_"))
+///     .with_shared(Schema { tables: vec!["users".to_string()] })
+///     .render_operation("tables.txt", tables);
+/// ```
+pub struct Shared<T>(Arc<T>);
+
+impl<T> Shared<T> {
+    /// Creates a new `Shared` wrapping the provided value
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to share
+    pub fn new(value: T) -> Shared<T> {
+        Shared(Arc::new(value))
+    }
+
+    /// Unwraps the `Shared` wrapper, returning the internal [`Arc`]
+    pub fn into_arc(self) -> Arc<T> {
+        self.0
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Shared<T> {
+        Shared(Arc::clone(&self.0))
+    }
+}
+
+impl<T> From<Arc<T>> for Shared<T> {
+    fn from(arc: Arc<T>) -> Self {
+        Shared(arc)
+    }
+}
+
+impl<T: Send + Sync + 'static> Extractor for Shared<T> {
+    fn extract(extractors: &Extractors) -> Result<Self, Error> {
+        extractors
+            .shared
+            .get(&TypeId::of::<T>())
+            .and_then(|value| Arc::clone(value).downcast::<T>().ok())
+            .map(Shared)
+            .ok_or(Error::ExtractorUnavailable(
+                std::any::type_name::<Shared<T>>(),
+            ))
+    }
+}
+
 /// Converts stored states into function parameters
 ///
 /// This trait enables conversion of state types into the parameter types
-/// expected by operation functions.
+/// expected by operation functions. An operation must take every state
+/// registered with `with_state`, in registration order, each as a `Data<_>`,
+/// optionally followed by [`Extractor`] parameters:
+///
+/// ```compile_fail
+/// use quickform::{state::Data, App};
+///
+/// async fn op(_age: Data<u32>, _name: Data<String>) -> u32 {
+///     0
+/// }
+///
+/// // The app provides `(Data<String>, Data<u32>)`, in that order
+/// let app = App::default()
+///     .with_state(String::from("Alice"))
+///     .with_state(30u32)
+///     .render_operation("age.jinja", op);
+/// ```
 ///
 /// # Type Parameters
 ///
 /// * `F` - The function signature that defines the parameter types
+#[diagnostic::on_unimplemented(
+    message = "the app provides state `{Self}`, which cannot be passed to an operation `{F}`",
+    label = "the operation's parameters do not match the app's state",
+    note = "operations must take every state added with `with_state`, in the same order, \
+            each wrapped in `Data<_>`, optionally followed by extractors such as `Fs`"
+)]
 pub trait IntoFunctionParams<F: FunctionSignature> {
     /// Converts self into the parameter types expected by the function
-    fn into_params(self) -> F::Params;
+    ///
+    /// # Arguments
+    ///
+    /// * `extractors` - What the app makes available to extractor parameters
+    ///
+    /// # Returns
+    ///
+    /// The parameters, or an error if an extractor parameter cannot be built
+    fn into_params(self, extractors: &Extractors) -> Result<F::Params, Error>;
 }
 
 // Macro for implementing IntoFunctionParams for different arities
@@ -194,8 +701,8 @@ macro_rules! impl_into_function_params {
         where
             F: FunctionSignature<Params = ()>
         {
-            fn into_params(self) -> F::Params {
-                ()
+            fn into_params(self, _: &Extractors) -> Result<F::Params, Error> {
+                Ok(())
             }
         }
     };
@@ -207,8 +714,8 @@ macro_rules! impl_into_function_params {
             F: FunctionSignature<Params = Data<$T>>,
             $T: Clone + Send + 'static,
         {
-            fn into_params(self) -> F::Params {
-                self
+            fn into_params(self, _: &Extractors) -> Result<F::Params, Error> {
+                Ok(self)
             }
         }
     };
@@ -220,8 +727,8 @@ macro_rules! impl_into_function_params {
             F: FunctionSignature<Params = ($(Data<$T>,)+)>,
             $($T: Clone + Send + 'static,)+
         {
-            fn into_params(self) -> F::Params {
-                self
+            fn into_params(self, _: &Extractors) -> Result<F::Params, Error> {
+                Ok(self)
             }
         }
     };
@@ -233,6 +740,138 @@ impl_into_function_params!(S1);
 impl_into_function_params!(S1, S2);
 impl_into_function_params!(S1, S2, S3);
 impl_into_function_params!(S1, S2, S3, S4);
+impl_into_function_params!(S1, S2, S3, S4, S5);
+impl_into_function_params!(S1, S2, S3, S4, S5, S6);
+impl_into_function_params!(S1, S2, S3, S4, S5, S6, S7);
+impl_into_function_params!(S1, S2, S3, S4, S5, S6, S7, S8);
+
+// Macro for implementing IntoFunctionParams for operations taking extractor
+// parameters after their state parameters
+macro_rules! impl_into_extractor_params {
+    // No state and a single extractor, which is passed on its own
+    (; ($E:ident)) => {
+        impl<$E, Fut> IntoFunctionParams<fn($E) -> Fut> for NoData
+        where
+            $E: Extractor,
+            Fut: Future,
+        {
+            fn into_params(self, extractors: &Extractors) -> Result<$E, Error> {
+                $E::extract(extractors)
+            }
+        }
+    };
+
+    // No state and several extractors
+    (; ($($E:ident),+)) => {
+        impl<$($E,)+ Fut> IntoFunctionParams<fn($($E),+) -> Fut> for NoData
+        where
+            $($E: Extractor,)+
+            Fut: Future,
+        {
+            fn into_params(self, extractors: &Extractors) -> Result<($($E,)+), Error> {
+                Ok(($($E::extract(extractors)?,)+))
+            }
+        }
+    };
+
+    // A single state
+    ($S:ident; ($($E:ident),+)) => {
+        impl<$S, $($E,)+ Fut> IntoFunctionParams<fn(Data<$S>, $($E),+) -> Fut> for Data<$S>
+        where
+            $S: Clone + Send + 'static,
+            $($E: Extractor,)+
+            Fut: Future,
+        {
+            fn into_params(
+                self,
+                extractors: &Extractors,
+            ) -> Result<(Data<$S>, $($E,)+), Error> {
+                Ok((self, $($E::extract(extractors)?,)+))
+            }
+        }
+    };
+
+    // Several states
+    ($(($S:ident, $s:ident)),+; ($($E:ident),+)) => {
+        impl<$($S,)+ $($E,)+ Fut> IntoFunctionParams<fn($(Data<$S>,)+ $($E),+) -> Fut>
+            for ($(Data<$S>,)+)
+        where
+            $($S: Clone + Send + 'static,)+
+            $($E: Extractor,)+
+            Fut: Future,
+        {
+            fn into_params(
+                self,
+                extractors: &Extractors,
+            ) -> Result<($(Data<$S>,)+ $($E,)+), Error> {
+                let ($($s,)+) = self;
+                Ok(($($s,)+ $($E::extract(extractors)?,)+))
+            }
+        }
+    };
+}
+
+// Implementations for every state count with one to three extractors, up to
+// 8 parameters in total
+macro_rules! impl_into_extractor_params_for {
+    ($($states:tt)*) => {
+        impl_into_extractor_params!($($states)*; (E1));
+        impl_into_extractor_params!($($states)*; (E1, E2));
+        impl_into_extractor_params!($($states)*; (E1, E2, E3));
+    };
+}
+
+impl_into_extractor_params_for!();
+impl_into_extractor_params_for!(S1);
+impl_into_extractor_params_for!((S1, s1), (S2, s2));
+impl_into_extractor_params_for!((S1, s1), (S2, s2), (S3, s3));
+impl_into_extractor_params_for!((S1, s1), (S2, s2), (S3, s3), (S4, s4));
+impl_into_extractor_params_for!((S1, s1), (S2, s2), (S3, s3), (S4, s4), (S5, s5));
+impl_into_extractor_params!((S1, s1), (S2, s2), (S3, s3), (S4, s4), (S5, s5), (S6, s6); (E1));
+impl_into_extractor_params!((S1, s1), (S2, s2), (S3, s3), (S4, s4), (S5, s5), (S6, s6); (E1, E2));
+impl_into_extractor_params!(
+    (S1, s1), (S2, s2), (S3, s3), (S4, s4), (S5, s5), (S6, s6), (S7, s7); (E1)
+);
+
+/// Reports the types held by a state tuple
+///
+/// Implemented for every state shape an [App](crate::App) can hold, so tooling
+/// can inspect which states were registered and in which order.
+pub trait StateTypes {
+    /// Returns the type name of each state, in registration order
+    fn type_names() -> Vec<&'static str>;
+}
+
+impl StateTypes for NoData {
+    fn type_names() -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+impl<S> StateTypes for Data<S> {
+    fn type_names() -> Vec<&'static str> {
+        vec![std::any::type_name::<S>()]
+    }
+}
+
+// Macro for implementing StateTypes for state tuples
+macro_rules! impl_state_types {
+    ($($T:ident),+) => {
+        impl<$($T),+> StateTypes for ($(Data<$T>,)+) {
+            fn type_names() -> Vec<&'static str> {
+                vec![$(std::any::type_name::<$T>()),+]
+            }
+        }
+    };
+}
+
+impl_state_types!(S1, S2);
+impl_state_types!(S1, S2, S3);
+impl_state_types!(S1, S2, S3, S4);
+impl_state_types!(S1, S2, S3, S4, S5);
+impl_state_types!(S1, S2, S3, S4, S5, S6);
+impl_state_types!(S1, S2, S3, S4, S5, S6, S7);
+impl_state_types!(S1, S2, S3, S4, S5, S6, S7, S8);
 
 #[cfg(test)]
 mod tests {
@@ -268,6 +907,106 @@ mod tests {
         assert_eq!(state.clone_inner().await.name, "Charlie");
     }
 
+    #[tokio::test]
+    async fn test_lock_guard() {
+        let state = Data::new(User {
+            name: "Alice".to_string(),
+        });
+
+        {
+            let mut guard = state.lock().await;
+            assert_eq!(guard.get().name, "Alice");
+            guard.get_mut().name.push_str(" Smith");
+            let renamed = format!("{}!", guard.get().name);
+            guard.set(User { name: renamed });
+        }
+
+        assert_eq!(state.clone_inner().await.name, "Alice Smith!");
+    }
+
+    #[tokio::test]
+    async fn test_lock_owned() {
+        let tokens = Data::new(Vec::new());
+
+        let mut guard = tokens.lock_owned().await;
+        let writer = tokio::spawn(async move {
+            for token in ["Hello", ", ", "world"] {
+                tokio::task::yield_now().await;
+                guard.push(token);
+            }
+        });
+        writer.await.unwrap();
+
+        assert_eq!(tokens.clone_inner().await.concat(), "Hello, world");
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_swap() {
+        let counter = Data::new(0);
+        let increment = |counter: Data<i32>| async move {
+            let mut current = counter.clone_inner().await;
+            while let Err(actual) = counter.compare_and_swap(&current, current + 1).await {
+                current = actual;
+            }
+        };
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| tokio::spawn(increment(counter.clone())))
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(counter.clone_inner().await, 8);
+        assert_eq!(counter.compare_and_swap(&0, 1).await, Err(8));
+    }
+
+    #[tokio::test]
+    async fn test_replace() {
+        let log = Data::new(vec!["fetched entities", "fetched relationships"]);
+
+        let old = log.replace(vec!["rendered"]).await;
+        assert_eq!(old, ["fetched entities", "fetched relationships"]);
+        assert_eq!(log.clone_inner().await, ["rendered"]);
+    }
+
+    #[tokio::test]
+    async fn test_try_update() {
+        let state = Data::new(User {
+            name: "Alice".to_string(),
+        });
+
+        let result = state
+            .try_update(|user| {
+                if user.name.is_empty() {
+                    return Err("name is empty");
+                }
+                user.name.push_str(" Smith");
+                Ok(())
+            })
+            .await;
+        assert_eq!(result, Ok(()));
+
+        let result = state
+            .try_update(|user| match user.name.len() {
+                len if len > 5 => Err(format!("name is {len} characters long")),
+                _ => Ok(()),
+            })
+            .await;
+        assert_eq!(result, Err("name is 11 characters long".to_string()));
+        assert_eq!(state.clone_inner().await.name, "Alice Smith");
+    }
+
+    #[tokio::test]
+    async fn test_with() {
+        let state = Data::new(User {
+            name: "Alice".to_string(),
+        });
+
+        assert_eq!(state.with(|user| user.name.len()).await, 5);
+        state.update(|user| user.name.push_str(" Smith")).await;
+        assert!(state.with(|user| user.name.ends_with("Smith")).await);
+    }
+
     #[tokio::test]
     async fn test_multiple_states() {
         let user_state = Data::new(User {
@@ -310,10 +1049,15 @@ mod tests {
 
     #[test]
     fn test_into_params() {
+        let extractors = Extractors::default();
+
         // Test NoData
         let no_state = NoData;
-        let _: () =
-            <NoData as IntoFunctionParams<fn() -> std::future::Ready<()>>>::into_params(no_state);
+        let _: () = <NoData as IntoFunctionParams<fn() -> std::future::Ready<()>>>::into_params(
+            no_state,
+            &extractors,
+        )
+        .unwrap();
 
         // Test single state
         let state = Data::new(User {
@@ -321,7 +1065,8 @@ mod tests {
         });
         let _: Data<User> = <Data<User> as IntoFunctionParams<
             fn(Data<User>) -> std::future::Ready<Data<User>>,
-        >>::into_params(state);
+        >>::into_params(state, &extractors)
+        .unwrap();
 
         // Test two states
         let user_state = Data::new(User {
@@ -333,6 +1078,67 @@ mod tests {
         let states = (user_state, config_state);
         let _: (Data<User>, Data<Config>) = <(Data<User>, Data<Config>) as IntoFunctionParams<
             fn((Data<User>, Data<Config>)) -> std::future::Ready<(Data<User>, Data<Config>)>,
-        >>::into_params(states);
+        >>::into_params(states, &extractors)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_type_names() {
+        assert!(NoData::type_names().is_empty());
+        assert_eq!(
+            Data::<User>::type_names(),
+            vec![std::any::type_name::<User>()]
+        );
+        assert_eq!(
+            <(Data<String>, Data<u32>)>::type_names(),
+            vec![
+                std::any::type_name::<String>(),
+                std::any::type_name::<u32>()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rwlock_interop() {
+        let lock = Arc::new(RwLock::new(1));
+        let data = Data::from(lock.clone());
+
+        data.set(2).await;
+        assert_eq!(*lock.read().await, 2);
+
+        let lock = data.into_rwlock();
+        *lock.write().await = 3;
+        assert_eq!(Data::from(lock).clone_inner().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_readers() {
+        let state = Data::new(User {
+            name: "Alice".to_string(),
+        });
+
+        // Both readers hold their guard until the other has acquired one too
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let reader = |state: Data<User>, barrier: Arc<tokio::sync::Barrier>| async move {
+            let guard = state.read().await;
+            barrier.wait().await;
+            guard.name.clone()
+        };
+        let readers = futures::future::join(
+            tokio::spawn(reader(state.clone(), barrier.clone())),
+            tokio::spawn(reader(state.clone(), barrier)),
+        );
+        let (first, second) = tokio::time::timeout(std::time::Duration::from_secs(5), readers)
+            .await
+            .expect("readers blocked each other");
+        assert_eq!(
+            (first.unwrap(), second.unwrap()),
+            ("Alice".into(), "Alice".into())
+        );
+
+        let guard = state.read().await;
+        assert!(state.try_write().is_err());
+        drop(guard);
+        assert!(state.try_write().is_ok());
     }
 }
@@ -1,25 +1,103 @@
-use crate::fs::MemFS;
+use crate::config::EngineConfig;
+use crate::fs::{MemFS, SharedMemFS};
+use crate::i18n::Translations;
 use crate::loader::memfs_loader;
-use minijinja::Environment;
+use minijinja::{Environment, Value};
 use serde::Serialize;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{Arc, PoisonError, RwLock};
 
+/// The template language an app renders its templates with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// MiniJinja, the default
+    #[default]
+    MiniJinja,
+    /// Tera, for templates written for it
+    ///
+    /// Every UTF-8 file in the template MemFS that parses as a Tera template
+    /// is loaded, so `include` and `extends` work across files. Other files,
+    /// such as assets containing `{{`, are only reported when rendered.
+    /// Operation outputs that are not maps fail to render, as Tera contexts
+    /// must be maps.
+    ///
+    /// Tera only takes keyword arguments, so templates call the built-in
+    /// functions as `ref(name="...")` and `t(key="...")`. The filters,
+    /// functions, globals, syntax and undefined behavior of an
+    /// [`EngineConfig`] are MiniJinja only, and so are `with_strict_undefined`
+    /// and `with_syntax`, which apply one: once any of them is applied to an
+    /// app, its Tera renders fail with an error saying so. Tera always treats
+    /// undefined variables as errors.
+    #[cfg(feature = "tera")]
+    Tera,
+}
+
+#[derive(Clone)]
 pub(crate) struct TemplateEngine<'a> {
     env: Environment<'a>,
+    #[cfg(feature = "tera")]
+    tera: Option<tera_engine::TeraEngine>,
+    /// Whether an [`EngineConfig`] that Tera can't apply was applied
+    #[cfg(feature = "tera")]
+    configured: bool,
 }
 
 impl<'a> TemplateEngine<'a> {
-    /// Creates a new empty template engine instance without a template directory
-    pub(crate) fn new() -> Self {
+    /// Creates a new template engine instance loading templates from a shared MemFS
+    ///
+    /// Names used by `include`, `extends` and `import` are first resolved
+    /// relative to the directory of the template using them, then against
+    /// the root of the MemFS. Relative names may use `./` and `../`.
+    pub(crate) fn from_memfs(fs: SharedMemFS) -> Self {
+        let mut env = Environment::new();
+        load_from(&mut env, fs);
         Self {
-            env: Environment::new(),
+            env,
+            #[cfg(feature = "tera")]
+            tera: None,
+            #[cfg(feature = "tera")]
+            configured: false,
         }
     }
 
-    /// Creates a new template engine instance from a MemFS
-    pub(crate) fn from_memfs(fs: MemFS) -> Self {
-        let mut env = Environment::new();
-        env.set_loader(memfs_loader(fs));
-        Self { env }
+    /// Returns a copy of the engine loading templates from another MemFS
+    ///
+    /// The copy keeps the engine's configuration but none of its compiled
+    /// templates.
+    pub(crate) fn rebind(&self, fs: SharedMemFS) -> Self {
+        let mut env = self.env.clone();
+        env.clear_templates();
+        load_from(&mut env, fs.clone());
+        #[cfg(not(feature = "tera"))]
+        let _ = fs;
+        Self {
+            env,
+            #[cfg(feature = "tera")]
+            tera: self.tera.as_ref().map(|_| tera_engine::TeraEngine::new(fs)),
+            #[cfg(feature = "tera")]
+            configured: self.configured,
+        }
+    }
+
+    /// Selects the template language templates are rendered with
+    #[cfg_attr(not(feature = "tera"), allow(unused_variables))]
+    pub(crate) fn set_engine(&mut self, engine: Engine, fs: SharedMemFS) {
+        #[cfg(feature = "tera")]
+        {
+            self.tera = (engine == Engine::Tera).then(|| tera_engine::TeraEngine::new(fs));
+        }
+    }
+
+    /// Drops all compiled templates so they are reloaded on next use
+    pub(crate) fn clear_cache(&mut self) {
+        self.env.clear_templates();
+        #[cfg(feature = "tera")]
+        if let Some(tera) = &self.tera {
+            tera.clear_cache();
+        }
     }
 
     /// Renders a template with the given context
@@ -28,7 +106,457 @@ impl<'a> TemplateEngine<'a> {
         template_name: &str,
         context: &T,
     ) -> Result<String, minijinja::Error> {
+        #[cfg(feature = "tera")]
+        if let Some(tera) = self.tera()? {
+            return tera.render(template_name, context);
+        }
         let tmpl = self.env.get_template(template_name)?;
         tmpl.render(context)
     }
+
+    /// Renders a one-off template source with the given context
+    ///
+    /// The source is compiled with the engine's configuration but not added
+    /// to it, so it can include or extend templates but not be included.
+    pub(crate) fn render_str<T: Serialize>(
+        &self,
+        source: &str,
+        context: &T,
+    ) -> Result<String, minijinja::Error> {
+        #[cfg(feature = "tera")]
+        if let Some(tera) = self.tera()? {
+            return tera.render_str(source, context);
+        }
+        self.env.render_str(source, context)
+    }
+
+    /// Renders a template with the given context into a writer
+    pub(crate) fn render_to_write<T: Serialize, W: Write>(
+        &self,
+        template_name: &str,
+        context: &T,
+        writer: W,
+    ) -> Result<(), minijinja::Error> {
+        #[cfg(feature = "tera")]
+        if let Some(tera) = self.tera()? {
+            return tera.render_to_write(template_name, context, writer);
+        }
+        let tmpl = self.env.get_template(template_name)?;
+        // `render_captured_to` replaces this in newer minijinja releases only
+        #[allow(deprecated)]
+        tmpl.render_to_write(context, writer)?;
+        Ok(())
+    }
+
+    /// Returns the Tera engine, if templates are rendered with Tera
+    ///
+    /// Fails if a MiniJinja only configuration was applied, rather than
+    /// rendering without it.
+    #[cfg(feature = "tera")]
+    fn tera(&self) -> Result<Option<&tera_engine::TeraEngine>, minijinja::Error> {
+        match &self.tera {
+            Some(_) if self.configured => Err(minijinja::Error::new(
+                minijinja::ErrorKind::InvalidOperation,
+                "the Tera engine doesn't support the filters, functions, globals, \
+                 syntax or undefined behavior of an EngineConfig",
+            )),
+            tera => Ok(tera.as_ref()),
+        }
+    }
+}
+
+/// Makes an environment load templates from a MemFS
+///
+/// Names used by `include`, `extends` and `import` are first resolved
+/// relative to the directory of the template using them.
+fn load_from(env: &mut Environment<'_>, fs: SharedMemFS) {
+    env.set_loader(memfs_loader(fs.clone()));
+    env.set_path_join_callback(move |name, parent| {
+        let Some((dir, _)) = parent.rsplit_once('/') else {
+            return Cow::Borrowed(name);
+        };
+        let relative = format!("{}/{}", dir, name);
+        let fs = fs.read().unwrap_or_else(PoisonError::into_inner);
+        if fs.read_file(&relative).is_ok() {
+            Cow::Owned(relative)
+        } else {
+            Cow::Borrowed(name)
+        }
+    });
+}
+
+/// The templates of an app and the engine rendering them
+///
+/// Shared by clones of an app until one of them changes its templates or
+/// engine configuration, which first gives it a copy of its own.
+pub(crate) struct TemplateSet {
+    pub(crate) fs: SharedMemFS,
+    pub(crate) engine: TemplateEngine<'static>,
+}
+
+impl TemplateSet {
+    /// Creates a template set rendering the templates of `fs`
+    pub(crate) fn new(fs: MemFS) -> Self {
+        let fs = Arc::new(RwLock::new(fs));
+        Self {
+            engine: TemplateEngine::from_memfs(fs.clone()),
+            fs,
+        }
+    }
+
+    /// Copies the templates, along with an engine loading from the copy
+    pub(crate) fn deep_clone(&self) -> Self {
+        let templates = self
+            .fs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let fs = Arc::new(RwLock::new(templates));
+        Self {
+            engine: self.engine.rebind(fs.clone()),
+            fs,
+        }
+    }
+}
+
+impl TemplateEngine<'static> {
+    /// Applies a shared engine configuration
+    pub(crate) fn configure(&mut self, config: &EngineConfig) {
+        self.add_builtins(config);
+        #[cfg(feature = "tera")]
+        {
+            self.configured = true;
+        }
+    }
+
+    /// Applies the configuration of a built-in function
+    ///
+    /// Unlike [`TemplateEngine::configure`], this doesn't prevent rendering
+    /// with Tera, which implements the built-in functions itself.
+    pub(crate) fn add_builtins(&mut self, config: &EngineConfig) {
+        config.apply(&mut self.env);
+        self.clear_cache();
+    }
+}
+
+/// A writer that buffers rendered output and fails once it grows past a limit
+///
+/// Used to abort runaway renders before they exhaust memory.
+pub(crate) struct LimitedWriter {
+    buf: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl LimitedWriter {
+    /// Creates a new writer accepting at most `limit` bytes
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            limit,
+            exceeded: false,
+        }
+    }
+
+    /// Whether a write was rejected because of the limit
+    pub(crate) fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    /// Consumes the writer, returning the buffered output
+    pub(crate) fn into_string(self) -> String {
+        String::from_utf8_lossy(&self.buf).into_owned()
+    }
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + data.len() > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("rendered output exceeds the size limit"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Values of the run being rendered that template functions read
+///
+/// The engine is shared by clones of an app, so functions such as `ref` and
+/// `t` can't capture what differs between runs. Instead, the app sets the
+/// scope on the rendering thread for the duration of every render.
+#[derive(Clone, Default)]
+pub(crate) struct RenderScope {
+    /// Outputs of the named operations that ran so far
+    pub(crate) named_outputs: Arc<RwLock<HashMap<String, Value>>>,
+    /// The message catalogs of the app
+    pub(crate) translations: Arc<Translations>,
+    /// The locale selected with `with_locale`, if any
+    pub(crate) locale: Option<String>,
+}
+
+thread_local! {
+    static SCOPE: RefCell<RenderScope> = RefCell::default();
+}
+
+impl RenderScope {
+    /// Runs a render with this scope set on the current thread
+    pub(crate) fn enter<R>(self, render: impl FnOnce() -> R) -> R {
+        let previous = SCOPE.replace(self);
+        let result = render();
+        SCOPE.set(previous);
+        result
+    }
+
+    /// Calls `f` with the scope of the render running on the current thread
+    pub(crate) fn with_current<R>(f: impl FnOnce(&RenderScope) -> R) -> R {
+        SCOPE.with_borrow(f)
+    }
+}
+
+#[cfg(feature = "tera")]
+mod tera_engine {
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    use minijinja::{Error, ErrorKind};
+    use serde::Serialize;
+
+    use super::RenderScope;
+    use crate::fs::SharedMemFS;
+
+    /// Renders templates from the MemFS with Tera
+    ///
+    /// Tera has no loader, so every UTF-8 file of the MemFS is parsed on first
+    /// use, and again after the cache is cleared. Files that fail to parse are
+    /// left out, and their parse error is reported when they are rendered.
+    /// The lock is only held while parsing, so renders run concurrently.
+    #[derive(Clone)]
+    pub(crate) struct TeraEngine {
+        fs: SharedMemFS,
+        tera: Arc<Mutex<Option<Arc<tera::Tera>>>>,
+    }
+
+    impl TeraEngine {
+        pub(crate) fn new(fs: SharedMemFS) -> Self {
+            Self {
+                fs,
+                tera: Arc::new(Mutex::new(None)),
+            }
+        }
+
+        /// Drops the parsed templates so they are parsed again on next use
+        pub(crate) fn clear_cache(&self) {
+            *self.tera.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        }
+
+        pub(crate) fn render<T: Serialize>(
+            &self,
+            name: &str,
+            context: &T,
+        ) -> Result<String, Error> {
+            let tera = self.parsed()?;
+            self.report(tera_context(context).and_then(|context| tera.render(name, &context)))
+        }
+
+        pub(crate) fn render_str<T: Serialize>(
+            &self,
+            source: &str,
+            context: &T,
+        ) -> Result<String, Error> {
+            // Rendering a one-off template adds it to the templates, so it
+            // is rendered by a copy
+            let mut scratch = tera::Tera::clone(&*self.parsed()?);
+            self.report(
+                tera_context(context).and_then(|context| scratch.render_str(source, &context)),
+            )
+        }
+
+        pub(crate) fn render_to_write<T: Serialize, W: Write>(
+            &self,
+            name: &str,
+            context: &T,
+            writer: W,
+        ) -> Result<(), Error> {
+            let tera = self.parsed()?;
+            self.report(
+                tera_context(context).and_then(|context| tera.render_to(name, &context, writer)),
+            )
+        }
+
+        /// Returns the parsed templates, parsing them if needed
+        fn parsed(&self) -> Result<Arc<tera::Tera>, Error> {
+            let mut cached = self.tera.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(tera) = &*cached {
+                return Ok(tera.clone());
+            }
+            let tera = Arc::new(self.parse().map_err(into_minijinja)?);
+            *cached = Some(tera.clone());
+            Ok(tera)
+        }
+
+        /// Reports the result of a render, with the parse error of a
+        /// template left out for failing to parse
+        fn report<R>(&self, result: tera::Result<R>) -> Result<R, Error> {
+            result
+                .map_err(|err| self.parse_error(&err).unwrap_or(err))
+                .map_err(into_minijinja)
+        }
+
+        /// Parses the UTF-8 files of the MemFS that are valid templates
+        fn parse(&self) -> tera::Result<tera::Tera> {
+            let fs = self.fs.read().unwrap_or_else(PoisonError::into_inner);
+            let templates: Vec<(String, String)> = fs
+                .walk()
+                .into_iter()
+                .filter_map(|path| {
+                    let source = fs.read_file_string(&path).ok()?;
+                    // A template that parses is kept even if the parent it
+                    // extends is missing, so the error names the parent
+                    let mut scratch = tera::Tera::default();
+                    let parsed = scratch.add_raw_template(&path, &source).is_ok()
+                        || scratch.get_template(&path).is_ok();
+                    parsed.then_some((path, source))
+                })
+                .collect();
+            let mut tera = tera::Tera::default();
+            tera.add_raw_templates(templates)?;
+            tera.register_function("ref", named_output);
+            tera.register_function("t", translate);
+            Ok(tera)
+        }
+
+        /// Returns the parse error of a template reported as missing because
+        /// it was left out for failing to parse
+        fn parse_error(&self, err: &tera::Error) -> Option<tera::Error> {
+            let tera::ErrorKind::TemplateNotFound(name) = &err.kind else {
+                return None;
+            };
+            let fs = self.fs.read().unwrap_or_else(PoisonError::into_inner);
+            let source = fs.read_file_string(name).ok()?;
+            tera::Tera::default().add_raw_template(name, &source).err()
+        }
+    }
+
+    /// `ref(name="...")`, the output of a named operation that already ran
+    fn named_output(args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let name = string_arg(args, "ref", "name")?;
+        let output = RenderScope::with_current(|scope| {
+            scope
+                .named_outputs
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(name)
+                .cloned()
+        })
+        .ok_or_else(|| {
+            tera::Error::msg(format!("no named operation called '{name}' has run yet"))
+        })?;
+        Ok(tera::to_value(output)?)
+    }
+
+    /// `t(key="...")`, the message for a key in the active locale
+    fn translate(args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let key = string_arg(args, "t", "key")?;
+        RenderScope::with_current(|scope| scope.translations.lookup(scope.locale.as_deref(), key))
+            .map(tera::Value::String)
+            .map_err(|err| tera::Error::msg(err.to_string()))
+    }
+
+    /// Returns the string argument `arg` of the function `function`
+    fn string_arg<'a>(
+        args: &'a HashMap<String, tera::Value>,
+        function: &str,
+        arg: &str,
+    ) -> tera::Result<&'a str> {
+        args.get(arg).and_then(tera::Value::as_str).ok_or_else(|| {
+            tera::Error::msg(format!("`{function}` takes a string `{arg}` argument"))
+        })
+    }
+
+    /// Converts a template context, which Tera requires to be a map
+    fn tera_context<T: Serialize>(context: &T) -> tera::Result<tera::Context> {
+        tera::Context::from_serialize(context)
+    }
+
+    /// Reports a Tera error as a template engine error
+    fn into_minijinja(err: tera::Error) -> Error {
+        let kind = match err.kind {
+            tera::ErrorKind::TemplateNotFound(_) => ErrorKind::TemplateNotFound,
+            _ => ErrorKind::InvalidOperation,
+        };
+        Error::new(kind, err.to_string()).with_source(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::MemFS;
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn test_relative_includes() {
+        let mut fs = MemFS::new();
+        fs.write_file(
+            "src/a/x.jinja",
+            b"{% include 'partial.jinja' %}+{% include 'shared.jinja' %}".to_vec(),
+        )
+        .unwrap();
+        fs.write_file("src/a/partial.jinja", b"a-partial".to_vec())
+            .unwrap();
+        fs.write_file("partial.jinja", b"root-partial".to_vec())
+            .unwrap();
+        fs.write_file("shared.jinja", b"root-shared".to_vec())
+            .unwrap();
+
+        let engine = TemplateEngine::from_memfs(Arc::new(RwLock::new(fs)));
+        let rendered = engine.render("src/a/x.jinja", &()).unwrap();
+        assert_eq!(rendered, "a-partial+root-shared");
+    }
+
+    #[test]
+    fn test_extends_and_nested_includes() {
+        let mut fs = MemFS::new();
+        fs.write_file(
+            "base.jinja",
+            b"<{% block body %}{% endblock %}>{% include 'footer.jinja' %}".to_vec(),
+        )
+        .unwrap();
+        fs.write_file("footer.jinja", b"root-footer".to_vec())
+            .unwrap();
+        fs.write_file(
+            "models/model.jinja",
+            b"{% extends 'base.jinja' %}\
+              {% block body %}{% include 'parts/fields.jinja' %}{% endblock %}"
+                .to_vec(),
+        )
+        .unwrap();
+        fs.write_file(
+            "models/parts/fields.jinja",
+            b"{% include 'field.jinja' %}+{% include '../header.jinja' %}".to_vec(),
+        )
+        .unwrap();
+        fs.write_file("models/parts/field.jinja", b"field".to_vec())
+            .unwrap();
+        fs.write_file("models/header.jinja", b"models-header".to_vec())
+            .unwrap();
+
+        let engine = TemplateEngine::from_memfs(Arc::new(RwLock::new(fs)));
+        let rendered = engine.render("models/model.jinja", &()).unwrap();
+        assert_eq!(rendered, "<field+models-header>root-footer");
+
+        // `..` can't reach above the root of the MemFS
+        let mut fs = MemFS::new();
+        fs.write_file("escape.jinja", b"{% include '../secret.jinja' %}".to_vec())
+            .unwrap();
+        let engine = TemplateEngine::from_memfs(Arc::new(RwLock::new(fs)));
+        let err = engine.render("escape.jinja", &()).unwrap_err();
+        assert!(matches!(err.kind(), minijinja::ErrorKind::TemplateNotFound));
+    }
 }
@@ -0,0 +1,54 @@
+//! Post-render transformation of rendered files
+//!
+//! A [`Transformer`] rewrites every rendered file before it is written, e.g. to
+//! format generated code or inject a license header. Transformers are
+//! registered with [`App::with_transformer`](crate::App::with_transformer) and
+//! run in registration order, each receiving the output of the previous one.
+
+use crate::Error;
+
+/// Rewrites rendered files before they are written
+///
+/// Implemented for closures taking the output path and the rendered content,
+/// so simple transformers don't need a type of their own.
+///
+/// # Examples
+///
+/// ```rust
+/// use quickform::{Error, Transformer};
+///
+/// struct LicenseHeader(&'static str);
+///
+/// impl Transformer for LicenseHeader {
+///     fn transform(&self, path: &str, content: String) -> Result<String, Error> {
+///         if path.ends_with(".rs") {
+///             Ok(format!("// {}\n{content}", self.0))
+///         } else {
+///             Ok(content)
+///         }
+///     }
+/// }
+/// ```
+pub trait Transformer: Send + Sync {
+    /// Transforms the content of a rendered file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path the file is written to
+    /// * `content` - The rendered content, as left by earlier transformers
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, Error>` - The new content, or an error aborting the
+    ///   run
+    fn transform(&self, path: &str, content: String) -> Result<String, Error>;
+}
+
+impl<F> Transformer for F
+where
+    F: Fn(&str, String) -> Result<String, Error> + Send + Sync,
+{
+    fn transform(&self, path: &str, content: String) -> Result<String, Error> {
+        self(path, content)
+    }
+}